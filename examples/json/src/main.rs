@@ -1,148 +1,48 @@
-use encoder::{Encoder};
+use encoder::{Encoder, EncoderOptions};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use serde_json::{Value};
-use udoc::{*, decoder::*, Reader};
+use udoc::*;
 
 fn encode_json(value: &Value) -> Vec<u8> {
-    let mut encoder = Encoder::default();
-    _encode_json(&mut encoder, value);
-    encoder.build().unwrap()
+    codec::encode(value).unwrap()
 }
 
 fn _encode_json(encoder: &mut Encoder, value: &Value) {
-    match value {
-        Value::Null => {
-            encoder.append_byte(WireType::Null as u8);
-        },
-        Value::Bool (value) => {
-            if *value {
-                encoder.append_byte(WireType::BoolTrue as u8);
-            }
-            else {
-                encoder.append_byte(WireType::BoolFalse as u8);
-            }
-        },
-        Value::Number (value) => {
-            encoder.append_byte(WireType::Float64 as u8);
-            encoder.append(&value.as_f64().unwrap().to_le_bytes());
-        },
-        Value::String (value) => {
-            encoder.append_byte(WireType::String as u8);
-            encoder.append_size(value.len() as u64);
-            encoder.append(value.as_bytes());
-        },
-        Value::Array (value) => {
-            encoder.append_byte(WireType::List as u8);
-
-            if value.len() > 0 {
-                encoder.begin_size();
-                encoder.append_size(value.len() as u64);
-                for entry in value {
-                    _encode_json(encoder, entry);
-                }
-                encoder.end_size();
-            }
-            else {
-                encoder.append_byte(0);
-            }
-        },
-        Value::Object (value) => {
-            encoder.append_byte(WireType::Null as u8 | WIRE_FLAG_TAGS);
-
-            if value.len() > 0 {
-                encoder.begin_size();
-                encoder.append_size(value.len() as u64);
-                for (k, v) in value {
-                    encoder.append_symbol(k.as_ref());
-                    _encode_json(encoder, v);
-                }
-                encoder.end_size();
-            }
-            else {
-                encoder.append_byte(0);
-            }
-        },
-    }
+    value.encode(encoder);
 }
 
+fn decode_json(buffer: &[u8]) -> Option<Value> {
+    codec::decode(buffer).ok()
+}
 
 
-// here errors could be useful. udoc may be valid, but schema can be violated.
-// though debug decoder is likely the better option.
 
-fn decode_json(buffer: &[u8]) -> Option<Value> {
-    let mut reader = Reader::new(buffer);
-    let value = _decode_json(&decode_value(&mut reader)?)?;
-    if reader.has_some() {
-        return None;
+// counts allocations, so benchmarks can report allocations per iteration.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
     }
-    Some(value)
-}
 
-fn _decode_json(value: &decoder::Value) -> Option<Value> {
-    use decoder::Payload::*;
-    let result = match value.payload {
-        Null => {
-            if value.header.has_tags {
-                let mut map = serde_json::Map::new();
-
-                let mut tags = value.tags()?;
-                for (symbol, value) in &mut tags {
-                    let symbol = std::str::from_utf8(symbol).ok()?.into();
-                    let value = _decode_json(&value)?;
-                    map.insert(symbol, value);
-                }
-                tags.check_error().ok()?;
-
-                Value::Object(map)
-            }
-            else {
-                Value::Null
-            }
-        },
-
-        Bool (value) => { Value::Bool(value) },
-
-        Float64 (value) => {
-            let number =
-                if value as u64 as f64 == value {
-                    if value >= 0.0 {
-                        serde_json::Number::from(value as u64)
-                    }
-                    else {
-                        serde_json::Number::from(value as i64)
-                    }
-                }
-                else {
-                    serde_json::Number::from_f64(value).unwrap()
-                };
-
-            Value::Number(number)
-        },
-
-        String (value) => {
-            Value::String(std::str::from_utf8(value).ok()?.into())
-        },
-
-        List (value) => {
-            let mut payload = ListDecoder::new(value)?;
-
-            let mut values = vec![];
-            values.reserve(payload.remaining);
-            for value in &mut payload {
-                values.push(_decode_json(&value)?);
-            }
-            payload.check_error().ok()?;
-
-            Value::Array(values)
-        },
-
-        _ => { return None },
-    };
-
-    Some(result)
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
 }
 
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
 
 
 const BENCH_DURATION: std::time::Duration = std::time::Duration::from_secs(2);
@@ -151,6 +51,7 @@ const BENCH_ITERS: usize = 10;
 fn bench<F: FnMut()>(name: &str, length: usize, mut f: F) {
     let mut iters = 0;
     let dt;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
     let t0 = std::time::Instant::now();
     loop {
         for _ in 0..BENCH_ITERS {
@@ -165,9 +66,12 @@ fn bench<F: FnMut()>(name: &str, length: usize, mut f: F) {
         }
     }
 
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
     let iters_per_sec = iters as f64 / dt.as_secs_f64();
     let mibs = length as f64 * iters_per_sec / (1024.0 * 1024.0);
-    println!("{} {:.2}/s {:.2?} {:.2}MiB/s", name, iters_per_sec, dt/iters, mibs);
+    let allocs = allocations as f64 / iters as f64;
+    println!("{} {:.2}/s {:.2?} {:.2}MiB/s {:.1} allocs", name, iters_per_sec, dt/iters, mibs, allocs);
 }
 
 
@@ -178,19 +82,21 @@ pub fn main() {
     let twitter = include_bytes!("twitter.json");
     let canada = include_bytes!("canada.json");
 
-    let v: Value = serde_json::from_slice(example).unwrap();
-    assert_eq!(v, decode_json(&encode_json(&v)).unwrap());
-
-    let v: Value = serde_json::from_slice(sleep).unwrap();
-    assert_eq!(v, decode_json(&encode_json(&v)).unwrap());
+    for text in [&example[..], &sleep[..], &twitter[..], &canada[..]] {
+        let v: Value = serde_json::from_slice(text).unwrap();
+        assert_eq!(v, decode_json(&encode_json(&v)).unwrap());
+    }
 
-    // TODO: broken?
-    //let v: Value = serde_json::from_slice(twitter).unwrap();
-    //assert_eq!(v, decode_json(&encode_json(&v)).unwrap());
+    // the streaming conversions agree with the tree.
+    for text in [&example[..], &sleep[..], &twitter[..], &canada[..]] {
+        let v: Value = serde_json::from_slice(text).unwrap();
+        let udoc = json::from_slice(text).unwrap();
+        assert_eq!(udoc, encode_json(&v));
 
-    // TODO: broken?
-    //let v: Value = serde_json::from_slice(canada).unwrap();
-    //assert_eq!(v, decode_json(&encode_json(&v)).unwrap());
+        let mut result = vec![];
+        json::to_writer(&mut result, &udoc).unwrap();
+        assert_eq!(v, serde_json::from_slice::<Value>(&result).unwrap());
+    }
 
 
     if 1 == 1 {
@@ -201,15 +107,27 @@ pub fn main() {
         });
     }
 
+    if 1 == 1 {
+        let v: Value = serde_json::from_slice(sleep).unwrap();
+        let length = encode_json(&v).len();
+        let mut encoder = Encoder::default();
+        let mut result = vec![];
+        bench("sleep encode compressed reuse", length, || {
+            result.clear();
+            _encode_json(&mut encoder, &v);
+            encoder.build_into(&mut result).unwrap();
+        });
+    }
+
     if 1 == 1 {
         let v: Value = serde_json::from_slice(sleep).unwrap();
         let length = {
-            let mut encoder = Encoder::new(4, false);
+            let mut encoder = Encoder::new(EncoderOptions::new().size_max_bytes(4).compress_sizes(false)).unwrap();
             _encode_json(&mut encoder, &v);
             encoder.build().unwrap().len()
         };
         bench("sleep encode uncompressed", length, || {
-            let mut encoder = Encoder::new(4, false);
+            let mut encoder = Encoder::new(EncoderOptions::new().size_max_bytes(4).compress_sizes(false)).unwrap();
             _encode_json(&mut encoder, &v);
             encoder.build().unwrap();
         });
@@ -226,9 +144,9 @@ pub fn main() {
     }
 
     if 1 == 1 {
+        let v: Value = serde_json::from_slice(sleep).unwrap();
         let udoc = encode_json(&v);
         let length = udoc.len();
-
         bench("sleep decode udoc", length, || {
             decode_json(&udoc).unwrap();
         });
@@ -249,6 +167,23 @@ pub fn main() {
         });
     }
 
+    if 1 == 1 {
+        let length = sleep.len();
+        bench("sleep transcode json", length, || {
+            json::from_slice(sleep).unwrap();
+        });
+    }
+
+    if 1 == 1 {
+        let udoc = json::from_slice(sleep).unwrap();
+        let length = sleep.len();
+        let mut result = vec![];
+        bench("sleep write json", length, || {
+            result.clear();
+            json::to_writer(&mut result, &udoc).unwrap();
+        });
+    }
+
 
     if 1 == 1 {
         let v: Value = serde_json::from_slice(twitter).unwrap();
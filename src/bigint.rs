@@ -0,0 +1,147 @@
+use num_bigint::{BigUint, BigInt};
+use crate::encoder::{Encoder, ValueBuilder};
+use crate::decoder::{DecodeError, Payload, Value, NumberError};
+use crate::codec::{self, Encode, Decode};
+
+
+// like the primitive integers, these use the narrowest wire type.
+
+impl<'enc> ValueBuilder<'enc> {
+    pub fn payload_biguint(self, value: &BigUint) {
+        match u128::try_from(value) {
+            Ok(value) => self.payload_unsigned(value),
+            Err(_)    => self.payload_nat(&value.to_bytes_le()),
+        }
+    }
+
+    pub fn payload_bigint(self, value: &BigInt) {
+        match i128::try_from(value) {
+            Ok(value) => self.payload_signed(value),
+            Err(_)    => self.payload_int(&value.to_signed_bytes_le()),
+        }
+    }
+}
+
+impl Encoder {
+    pub fn write_biguint(&mut self, value: &BigUint) {
+        self.value().payload_biguint(value);
+    }
+
+    pub fn write_bigint(&mut self, value: &BigInt) {
+        self.value().payload_bigint(value);
+    }
+}
+
+
+impl<'val> Value<'val> {
+    // accept any integer wire type, like `as_u128`.
+    pub fn as_biguint(&self) -> Result<BigUint, NumberError> {
+        match self.payload {
+            Payload::Nat (bytes) => Ok(BigUint::from_bytes_le(bytes)),
+            Payload::Int (bytes) => {
+                BigInt::from_signed_bytes_le(bytes).to_biguint()
+                    .ok_or(NumberError::OutOfRange(self.header.wire_type))
+            },
+            _ => self.as_u128().map(BigUint::from),
+        }
+    }
+
+    pub fn as_bigint(&self) -> Result<BigInt, NumberError> {
+        match self.payload {
+            Payload::Nat (bytes) => Ok(BigUint::from_bytes_le(bytes).into()),
+            Payload::Int (bytes) => Ok(BigInt::from_signed_bytes_le(bytes)),
+            _ => self.as_i128().map(BigInt::from),
+        }
+    }
+}
+
+
+impl Encode for BigUint {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_biguint(self);
+    }
+}
+
+impl<'de> Decode<'de> for BigUint {
+    fn decode(value: &Value<'de>) -> Result<BigUint, DecodeError> {
+        value.as_biguint().map_err(|e| codec::number_error(e, "integer", value))
+    }
+}
+
+impl Encode for BigInt {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_bigint(self);
+    }
+}
+
+impl<'de> Decode<'de> for BigInt {
+    fn decode(value: &Value<'de>) -> Result<BigInt, DecodeError> {
+        value.as_bigint().map_err(|e| codec::number_error(e, "integer", value))
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slice_reader::Reader;
+    use crate::wire_type::WireType;
+    use crate::decoder::decode_document;
+
+    fn round_trip<T: Encode + for<'de> Decode<'de> + PartialEq + std::fmt::Debug>(value: &T) -> Vec<u8> {
+        let bytes = codec::encode(value).unwrap();
+        assert_eq!(&codec::decode::<T>(&bytes).unwrap(), value);
+        bytes
+    }
+
+    #[test]
+    fn narrowest_wire_type() {
+        assert_eq!(round_trip(&BigUint::from(5u8)), [WireType::Nat8 as u8, 5]);
+        assert_eq!(round_trip(&BigInt::from(-1)),   [WireType::Int8 as u8, 0xff]);
+        assert_eq!(round_trip(&BigInt::from(-128)), [WireType::Int8 as u8, 0x80]);
+        assert_eq!(round_trip(&BigInt::from(-129)), [WireType::Int16 as u8, 0x7f, 0xff]);
+        assert_eq!(round_trip(&BigUint::from(u128::MAX)), codec::encode(&u128::MAX).unwrap());
+        assert_eq!(round_trip(&BigInt::from(i128::MIN)),  codec::encode(&i128::MIN).unwrap());
+    }
+
+    #[test]
+    fn wide_values() {
+        let max = (BigUint::from(1u8) << 256u32) - 1u8;
+        let bytes = round_trip(&max);
+        assert_eq!(bytes[..2], [WireType::Nat as u8, 32 << 2]);
+        assert_eq!(bytes[2..], [0xff; 32]);
+
+        let min = -(BigInt::from(1) << 255u32);
+        let bytes = round_trip(&min);
+        assert_eq!(bytes[..2], [WireType::Int as u8, 32 << 2]);
+        assert_eq!(bytes[2..33], [0; 31]);
+        assert_eq!(bytes[33], 0x80);
+
+        round_trip(&(BigInt::from(1) << 255u32));
+        round_trip(&(BigUint::from(u128::MAX) + 1u8));
+    }
+
+    #[test]
+    fn conversions() {
+        let mut encoder = Encoder::default();
+        encoder.write_int(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80]);
+        let bytes = encoder.build().unwrap();
+        let value = decode_document(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(value.as_bigint(), Ok(-(BigInt::from(1) << 135u32)));
+        assert_eq!(value.as_biguint(), Err(NumberError::OutOfRange(WireType::Int)));
+
+        // redundant high bytes, as other encoders may write them.
+        let mut encoder = Encoder::default();
+        encoder.append_byte(WireType::Int as u8);
+        encoder.append_size_prefixed(&[0x7f, 0xff, 0xff, 0xff]);
+        let bytes = encoder.build().unwrap();
+        let value = decode_document(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(value.as_bigint(), Ok(BigInt::from(-129)));
+
+        let bytes = codec::encode(&-1i8).unwrap();
+        let value = decode_document(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(value.as_biguint(), Err(NumberError::OutOfRange(WireType::Int8)));
+        assert_eq!(value.as_bigint(), Ok(BigInt::from(-1)));
+    }
+}
@@ -0,0 +1,871 @@
+use std::collections::{HashMap, BTreeMap};
+use std::hash::{Hash, BuildHasher};
+use std::rc::Rc;
+use std::sync::Arc;
+use slice_reader::Reader;
+use crate::wire_type::WireType;
+use crate::utils::offset_in;
+use crate::encoder::{self, Encoder};
+use crate::decoder::{self, DecodeError, Stage, Reason, Payload, Value, DecodeOptions, NumberError, ListDecoder, TagDecoder};
+use crate::value::{self, OwnedValue};
+use crate::decimal::{Decimal32, Decimal64};
+
+
+// typed encoding without going through serde. usually derived with
+// `#[derive(UdocEncode, UdocDecode)]`, which uses the same layout as `ser`:
+//  - structs are `Null` values with tags, tuple structs are lists.
+//    single field tuple structs without a kind are written as their field.
+//  - enum variants are written with the variant name as the kind symbol.
+//    unit variants are `Null`, newtype variants are single element lists,
+//    tuple variants are lists, and struct variants are `Null` with tags.
+//  - integers use the narrowest `Nat*`/`Int*` wire type.
+
+pub trait Encode {
+    fn encode(&self, encoder: &mut Encoder);
+}
+
+// error offsets are relative to the document's root. a well formed value
+// with the wrong type, kind, or length for `Self` fails with
+// `Reason::InvalidValue`, at the value's offset.
+pub trait Decode<'de>: Sized {
+    fn decode(value: &Value<'de>) -> Result<Self, DecodeError>;
+}
+
+
+pub fn encode<T: Encode + ?Sized>(value: &T) -> Result<Vec<u8>, encoder::Error> {
+    let mut encoder = Encoder::default();
+    value.encode(&mut encoder);
+    encoder.build()
+}
+
+pub fn decode<'de, T: Decode<'de>>(buffer: &'de [u8]) -> Result<T, DecodeError> {
+    decode_with_options(buffer, &DecodeOptions::new())
+}
+
+pub fn decode_with_options<'de, T: Decode<'de>>(buffer: &'de [u8], options: &DecodeOptions<'de>) -> Result<T, DecodeError> {
+    let mut reader = Reader::new(buffer);
+    let value = decoder::decode_document_with_options(&mut reader, options)?;
+    let result = T::decode(&value)?;
+    if reader.has_some() {
+        return Err(DecodeError::new(Stage::Trailing, Reason::TrailingBytes, &reader));
+    }
+    Ok(result)
+}
+
+
+fn expect_null(value: &Value) -> Result<(), DecodeError> {
+    match value.payload {
+        Payload::Null if !value.header.has_tags => Ok(()),
+        _ => Err(DecodeError::unexpected_type("null", value)),
+    }
+}
+
+
+pub struct Tags<'de> {
+    tags: TagDecoder<'de>,
+}
+
+impl<'de> Tags<'de> {
+    // `value` must be `Null` with tags.
+    pub fn new(value: &Value<'de>) -> Result<Tags<'de>, DecodeError> {
+        if !value.header.has_tags || !matches!(value.payload, Payload::Null) {
+            return Err(DecodeError::unexpected_type("null with tags", value));
+        }
+
+        value.tags().map(|tags| Tags { tags })
+    }
+
+    // the number of tags not yet decoded.
+    pub fn remaining(&self) -> usize {
+        self.tags.remaining
+    }
+
+    pub fn finish(self) -> Result<(), DecodeError> {
+        self.tags.check_error()
+    }
+}
+
+impl<'de> Iterator for Tags<'de> {
+    type Item = (&'de [u8], Value<'de>);
+
+    fn next(&mut self) -> Option<(&'de [u8], Value<'de>)> {
+        self.tags.next()
+    }
+}
+
+
+pub struct List<'de> {
+    length: usize,
+    list:   ListDecoder<'de>,
+    // the list value, for errors.
+    value:  Value<'de>,
+}
+
+impl<'de> List<'de> {
+    pub fn new(value: &Value<'de>) -> Result<List<'de>, DecodeError> {
+        let list = match value.payload {
+            Payload::List (list) if !value.header.has_tags => list,
+            _ => return Err(DecodeError::unexpected_type("list", value)),
+        };
+
+        let list = ListDecoder::new(list, value.document, value.depth)?;
+        Ok(List { length: list.remaining, list, value: *value })
+    }
+
+    pub fn with_len(value: &Value<'de>, length: usize) -> Result<List<'de>, DecodeError> {
+        let list = List::new(value)?;
+        if list.length != length {
+            return Err(DecodeError::unexpected_length(value, length, list.length));
+        }
+        Ok(list)
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    // reading past the end fails with `Invalid::UnexpectedLength`.
+    pub fn element(&mut self) -> Result<Value<'de>, DecodeError> {
+        if let Some(value) = self.list.next() {
+            return Ok(value);
+        }
+        match self.list.take_element_error() {
+            Some(error) => Err(error),
+            None        => Err(DecodeError::unexpected_length(&self.value, self.length + 1, self.length)),
+        }
+    }
+
+    pub fn finish(self) -> Result<(), DecodeError> {
+        self.list.check_error()
+    }
+}
+
+
+// `#[udoc(wire = "...")]` fields are read with these, and written with the
+// matching `Encoder` method. the field type converts with `Into` on write
+// and `TryFrom` on read, and reading requires exactly the declared wire type.
+pub mod wire {
+    use super::*;
+
+    macro_rules! fixed {
+        ($read: ident, $variant: ident, $ty: ty, $name: expr) => {
+            pub fn $read<'de, T: TryFrom<$ty>>(value: &Value<'de>) -> Result<T, DecodeError> {
+                match value.payload {
+                    Payload::$variant (raw) => T::try_from(raw).map_err(|_| DecodeError::out_of_range(value)),
+                    _ => Err(DecodeError::unexpected_type($name, value)),
+                }
+            }
+        };
+    }
+
+    fixed!(bool,      Bool,      bool,      "bool");
+    fixed!(nat8,      Nat8,      u8,        "nat8");
+    fixed!(nat16,     Nat16,     u16,       "nat16");
+    fixed!(nat32,     Nat32,     u32,       "nat32");
+    fixed!(nat64,     Nat64,     u64,       "nat64");
+    fixed!(int8,      Int8,      i8,        "int8");
+    fixed!(int16,     Int16,     i16,       "int16");
+    fixed!(int32,     Int32,     i32,       "int32");
+    fixed!(int64,     Int64,     i64,       "int64");
+    fixed!(float32,   Float32,   f32,       "float32");
+    fixed!(float64,   Float64,   f64,       "float64");
+    fixed!(decimal32, Decimal32, Decimal32, "decimal32");
+    fixed!(decimal64, Decimal64, Decimal64, "decimal64");
+
+    pub fn string<'de, T: TryFrom<&'de str>>(value: &Value<'de>) -> Result<T, DecodeError> {
+        match value.payload {
+            Payload::String (string) => {
+                let string = std::str::from_utf8(string).map_err(|e| {
+                    let base = offset_in(value.document.root, string);
+                    DecodeError::invalid_utf8(value.header, string, e).rebase(base)
+                })?;
+                T::try_from(string).map_err(|_| DecodeError::out_of_range(value))
+            },
+            _ => Err(DecodeError::unexpected_type("string", value)),
+        }
+    }
+
+    pub fn bytes<'de, T: TryFrom<&'de [u8]>>(value: &Value<'de>) -> Result<T, DecodeError> {
+        match value.payload {
+            Payload::Bytes (bytes) => T::try_from(bytes).map_err(|_| DecodeError::out_of_range(value)),
+            _ => Err(DecodeError::unexpected_type("bytes", value)),
+        }
+    }
+
+    pub fn symbol<'de, T: TryFrom<&'de [u8]>>(value: &Value<'de>) -> Result<T, DecodeError> {
+        match value.payload {
+            Payload::Symbol (symbol) => T::try_from(symbol).map_err(|_| DecodeError::out_of_range(value)),
+            _ => Err(DecodeError::unexpected_type("symbol", value)),
+        }
+    }
+}
+
+
+
+pub(crate) fn number_error(error: NumberError, expected: &'static str, value: &Value) -> DecodeError {
+    match error {
+        NumberError::UnexpectedType (_) => DecodeError::unexpected_type(expected, value),
+        NumberError::OutOfRange     (_) => DecodeError::out_of_range(value),
+    }
+}
+
+macro_rules! unsigned {
+    ($($ty: ty),*) => { $(
+        impl Encode for $ty {
+            fn encode(&self, encoder: &mut Encoder) {
+                encoder.write_unsigned(*self as u128);
+            }
+        }
+
+        impl<'de> Decode<'de> for $ty {
+            fn decode(value: &Value<'de>) -> Result<$ty, DecodeError> {
+                let result = value.as_u128().map_err(|e| number_error(e, "integer", value))?;
+                <$ty>::try_from(result).map_err(|_| DecodeError::out_of_range(value))
+            }
+        }
+    )* };
+}
+
+macro_rules! signed {
+    ($($ty: ty),*) => { $(
+        impl Encode for $ty {
+            fn encode(&self, encoder: &mut Encoder) {
+                encoder.write_signed(*self as i128);
+            }
+        }
+
+        impl<'de> Decode<'de> for $ty {
+            fn decode(value: &Value<'de>) -> Result<$ty, DecodeError> {
+                let result = value.as_i128().map_err(|e| number_error(e, "integer", value))?;
+                <$ty>::try_from(result).map_err(|_| DecodeError::out_of_range(value))
+            }
+        }
+    )* };
+}
+
+unsigned!(u8, u16, u32, u64, u128, usize);
+signed!(i8, i16, i32, i64, i128, isize);
+
+
+impl Encode for bool {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_bool(*self);
+    }
+}
+
+impl<'de> Decode<'de> for bool {
+    fn decode(value: &Value<'de>) -> Result<bool, DecodeError> {
+        wire::bool(value)
+    }
+}
+
+impl Encode for f32 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_f32(*self);
+    }
+}
+
+impl<'de> Decode<'de> for f32 {
+    fn decode(value: &Value<'de>) -> Result<f32, DecodeError> {
+        wire::float32(value)
+    }
+}
+
+impl Encode for f64 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_f64(*self);
+    }
+}
+
+impl<'de> Decode<'de> for f64 {
+    fn decode(value: &Value<'de>) -> Result<f64, DecodeError> {
+        value.as_f64().map_err(|e| number_error(e, "number", value))
+    }
+}
+
+impl Encode for Decimal32 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_decimal32(*self);
+    }
+}
+
+impl<'de> Decode<'de> for Decimal32 {
+    fn decode(value: &Value<'de>) -> Result<Decimal32, DecodeError> {
+        wire::decimal32(value)
+    }
+}
+
+impl Encode for Decimal64 {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_decimal64(*self);
+    }
+}
+
+impl<'de> Decode<'de> for Decimal64 {
+    fn decode(value: &Value<'de>) -> Result<Decimal64, DecodeError> {
+        match value.payload {
+            Payload::Decimal32 (value) => Ok(value.into()),
+            Payload::Decimal64 (value) => Ok(value),
+            _ => Err(DecodeError::unexpected_type("decimal", value)),
+        }
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_string(self);
+    }
+}
+
+impl<'de> Decode<'de> for String {
+    fn decode(value: &Value<'de>) -> Result<String, DecodeError> {
+        wire::string(value)
+    }
+}
+
+impl Encode for () {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_null();
+    }
+}
+
+impl<'de> Decode<'de> for () {
+    fn decode(value: &Value<'de>) -> Result<(), DecodeError> {
+        expect_null(value)
+    }
+}
+
+impl Encode for OwnedValue {
+    fn encode(&self, encoder: &mut Encoder) {
+        OwnedValue::encode(self, encoder);
+    }
+}
+
+impl<'de> Decode<'de> for OwnedValue {
+    fn decode(value: &Value<'de>) -> Result<OwnedValue, DecodeError> {
+        value::to_owned(*value)
+    }
+}
+
+
+
+// note: `[u8]` and `&[u8]` are bytes. `Vec<T>`, arrays and tuples are
+// lists, including `Vec<u8>`.
+
+impl Encode for str {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_string(self);
+    }
+}
+
+impl<'de: 'a, 'a> Decode<'de> for &'a str {
+    fn decode(value: &Value<'de>) -> Result<&'a str, DecodeError> {
+        wire::string(value)
+    }
+}
+
+impl Encode for [u8] {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.write_bytes(self);
+    }
+}
+
+impl<'de: 'a, 'a> Decode<'de> for &'a [u8] {
+    fn decode(value: &Value<'de>) -> Result<&'a [u8], DecodeError> {
+        wire::bytes(value)
+    }
+}
+
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.value().payload_list(self.len(), |encoder| {
+            for value in self {
+                value.encode(encoder);
+            }
+        });
+    }
+}
+
+impl<'de, T: Decode<'de>> Decode<'de> for Vec<T> {
+    fn decode(value: &Value<'de>) -> Result<Vec<T>, DecodeError> {
+        let mut list = List::new(value)?;
+
+        let mut result = Vec::with_capacity(list.len());
+        for index in 0..list.len() {
+            result.push(T::decode(&list.element()?).map_err(|e| e.in_index(index))?);
+        }
+        list.finish()?;
+
+        Ok(result)
+    }
+}
+
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.value().payload_list(N, |encoder| {
+            for value in self {
+                value.encode(encoder);
+            }
+        });
+    }
+}
+
+impl<'de, T: Decode<'de>, const N: usize> Decode<'de> for [T; N] {
+    fn decode(value: &Value<'de>) -> Result<[T; N], DecodeError> {
+        let mut list = List::with_len(value, N)?;
+
+        let mut result = Vec::with_capacity(N);
+        for index in 0..N {
+            result.push(T::decode(&list.element()?).map_err(|e| e.in_index(index))?);
+        }
+        list.finish()?;
+
+        // note: the length was checked above.
+        Ok(result.try_into().ok().unwrap())
+    }
+}
+
+
+macro_rules! tuple {
+    ($length: expr; $($name: ident $index: tt),+) => {
+        impl<$($name: Encode),+> Encode for ($($name,)+) {
+            fn encode(&self, encoder: &mut Encoder) {
+                encoder.value().payload_list($length, |encoder| {
+                    $(self.$index.encode(encoder);)+
+                });
+            }
+        }
+
+        impl<'de, $($name: Decode<'de>),+> Decode<'de> for ($($name,)+) {
+            fn decode(value: &Value<'de>) -> Result<($($name,)+), DecodeError> {
+                let mut list = List::with_len(value, $length)?;
+                let result = ($(
+                    $name::decode(&list.element()?).map_err(|e| e.in_index($index))?,
+                )+);
+                list.finish()?;
+                Ok(result)
+            }
+        }
+    };
+}
+
+tuple!( 1; A 0);
+tuple!( 2; A 0, B 1);
+tuple!( 3; A 0, B 1, C 2);
+tuple!( 4; A 0, B 1, C 2, D 3);
+tuple!( 5; A 0, B 1, C 2, D 3, E 4);
+tuple!( 6; A 0, B 1, C 2, D 3, E 4, F 5);
+tuple!( 7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuple!( 8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+tuple!( 9; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+tuple!(10; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+tuple!(11; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+tuple!(12; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+
+// `None` is `Null`. a `Null` with a kind or tags decodes as `Some`.
+// note: `Some` of a value that is written as a plain `Null`, like `Some(())`
+// or `Some(None)`, can't be told apart from `None` and decodes as `None`.
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Some(value) => value.encode(encoder),
+            None        => encoder.write_null(),
+        }
+    }
+}
+
+impl<'de, T: Decode<'de>> Decode<'de> for Option<T> {
+    fn decode(value: &Value<'de>) -> Result<Option<T>, DecodeError> {
+        let header = value.header;
+        if header.wire_type == WireType::Null && !header.has_kind && !header.has_tags {
+            return Ok(None);
+        }
+        Ok(Some(T::decode(value)?))
+    }
+}
+
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, encoder: &mut Encoder) {
+        (**self).encode(encoder);
+    }
+}
+
+macro_rules! pointer {
+    ($($ty: ident),*) => { $(
+        impl<T: Encode + ?Sized> Encode for $ty<T> {
+            fn encode(&self, encoder: &mut Encoder) {
+                (**self).encode(encoder);
+            }
+        }
+
+        impl<'de, T: Decode<'de>> Decode<'de> for $ty<T> {
+            fn decode(value: &Value<'de>) -> Result<$ty<T>, DecodeError> {
+                Ok($ty::new(T::decode(value)?))
+            }
+        }
+    )* };
+}
+
+pointer!(Box, Rc, Arc);
+
+
+// maps are `Null` values with tags. the keys are the tag symbols.
+
+pub trait EncodeSymbol {
+    fn symbol(&self) -> &[u8];
+}
+
+// `None` for symbols `Self` can't represent.
+pub trait DecodeSymbol<'de>: Sized {
+    fn decode_symbol(symbol: &'de [u8]) -> Option<Self>;
+}
+
+impl EncodeSymbol for str {
+    fn symbol(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl EncodeSymbol for String {
+    fn symbol(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl EncodeSymbol for [u8] {
+    fn symbol(&self) -> &[u8] {
+        self
+    }
+}
+
+impl EncodeSymbol for Vec<u8> {
+    fn symbol(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<T: EncodeSymbol + ?Sized> EncodeSymbol for &T {
+    fn symbol(&self) -> &[u8] {
+        (**self).symbol()
+    }
+}
+
+impl<'de: 'a, 'a> DecodeSymbol<'de> for &'a str {
+    fn decode_symbol(symbol: &'de [u8]) -> Option<&'a str> {
+        std::str::from_utf8(symbol).ok()
+    }
+}
+
+impl<'de> DecodeSymbol<'de> for String {
+    fn decode_symbol(symbol: &'de [u8]) -> Option<String> {
+        <&str>::decode_symbol(symbol).map(String::from)
+    }
+}
+
+impl<'de: 'a, 'a> DecodeSymbol<'de> for &'a [u8] {
+    fn decode_symbol(symbol: &'de [u8]) -> Option<&'a [u8]> {
+        Some(symbol)
+    }
+}
+
+impl<'de> DecodeSymbol<'de> for Vec<u8> {
+    fn decode_symbol(symbol: &'de [u8]) -> Option<Vec<u8>> {
+        Some(symbol.to_vec())
+    }
+}
+
+fn encode_map<'a, K, V, I>(encoder: &mut Encoder, entries: I)
+where K: EncodeSymbol + 'a, V: Encode + 'a, I: Iterator<Item = (&'a K, &'a V)> {
+    let mut builder = encoder.value().with_tags();
+    for (key, value) in entries {
+        builder = builder.tag(key.symbol(), |encoder| value.encode(encoder));
+    }
+    builder.payload_null();
+}
+
+fn decode_map<'de, K, V, F>(map: &Value<'de>, mut insert: F) -> Result<(), DecodeError>
+where K: DecodeSymbol<'de>, V: Decode<'de>, F: FnMut(K, V) {
+    let mut tags = Tags::new(map)?;
+    for (symbol, value) in &mut tags {
+        let key   = K::decode_symbol(symbol).ok_or_else(|| DecodeError::invalid_tag(map, symbol))?;
+        let value = V::decode(&value).map_err(|e| e.in_tag(symbol))?;
+        insert(key, value);
+    }
+    tags.finish()
+}
+
+impl<K: EncodeSymbol, V: Encode, S> Encode for HashMap<K, V, S> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encode_map(encoder, self.iter());
+    }
+}
+
+impl<'de, K, V, S> Decode<'de> for HashMap<K, V, S>
+where K: DecodeSymbol<'de> + Eq + Hash, V: Decode<'de>, S: BuildHasher + Default {
+    fn decode(value: &Value<'de>) -> Result<HashMap<K, V, S>, DecodeError> {
+        let mut result = HashMap::default();
+        decode_map(value, |key, value| { result.insert(key, value); })?;
+        Ok(result)
+    }
+}
+
+impl<K: EncodeSymbol, V: Encode> Encode for BTreeMap<K, V> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encode_map(encoder, self.iter());
+    }
+}
+
+impl<'de, K, V> Decode<'de> for BTreeMap<K, V>
+where K: DecodeSymbol<'de> + Ord, V: Decode<'de> {
+    fn decode(value: &Value<'de>) -> Result<BTreeMap<K, V>, DecodeError> {
+        let mut result = BTreeMap::new();
+        decode_map(value, |key, value| { result.insert(key, value); })?;
+        Ok(result)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+    use crate::decoder::{Invalid, Path, PathSegment};
+
+    fn round_trip<T: Encode + for<'de> Decode<'de> + PartialEq + Debug>(value: &T) {
+        let bytes = encode(value).unwrap();
+        assert_eq!(&decode::<T>(&bytes).unwrap(), value);
+    }
+
+    fn build<F: FnOnce(&mut Encoder)>(f: F) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        f(&mut encoder);
+        encoder.build().unwrap()
+    }
+
+    // the reason, wire type, offset and path of the error, which must
+    // locate a value.
+    fn error<T: for<'de> Decode<'de> + Debug>(bytes: &[u8]) -> (Reason, Option<WireType>, usize, Path) {
+        located(bytes, decode::<T>(bytes).unwrap_err())
+    }
+
+    fn located(bytes: &[u8], error: DecodeError) -> (Reason, Option<WireType>, usize, Path) {
+        assert_eq!(error.header, Some(bytes[error.offset]));
+        (error.reason, error.wire_type, error.offset, error.path)
+    }
+
+    fn invalid(invalid: Invalid, wire_type: WireType, offset: usize, path: Vec<PathSegment>) -> (Reason, Option<WireType>, usize, Path) {
+        (Reason::InvalidValue(invalid), Some(wire_type), offset, Path(path))
+    }
+
+
+    #[test]
+    fn integers() {
+        round_trip(&u8::MAX);
+        round_trip(&u16::MAX);
+        round_trip(&u32::MAX);
+        round_trip(&u64::MAX);
+        round_trip(&u128::MAX);
+        round_trip(&usize::MAX);
+        for value in [i128::MIN, i64::MIN as i128, -1, 0, i64::MAX as i128, i128::MAX] {
+            round_trip(&value);
+        }
+        round_trip(&i8::MIN);
+        round_trip(&i16::MIN);
+        round_trip(&i32::MIN);
+        round_trip(&i64::MIN);
+        round_trip(&isize::MIN);
+
+        // any integer wire type that fits.
+        assert_eq!(decode::<u8>(&encode(&200i64).unwrap()).unwrap(), 200);
+        assert_eq!(decode::<i8>(&encode(&-5i128).unwrap()).unwrap(), -5);
+
+        assert_eq!(error::<u8>(&encode(&300u16).unwrap()),
+                   invalid(Invalid::OutOfRange, WireType::Nat16, 0, vec![]));
+        assert_eq!(error::<u32>(&encode(&-1i8).unwrap()),
+                   invalid(Invalid::OutOfRange, WireType::Int8, 0, vec![]));
+        assert_eq!(error::<i64>(&encode(&u128::MAX).unwrap()),
+                   invalid(Invalid::OutOfRange, WireType::Nat, 0, vec![]));
+        assert_eq!(error::<u8>(&encode("1").unwrap()),
+                   invalid(Invalid::UnexpectedType { expected: "integer" }, WireType::String, 0, vec![]));
+    }
+
+    #[test]
+    fn floats() {
+        round_trip(&1.5f32);
+        round_trip(&-0.25f64);
+        round_trip(&f64::MAX);
+        let bytes = encode(&f64::NAN).unwrap();
+        assert!(decode::<f64>(&bytes).unwrap().is_nan());
+
+        // `f64` widens, `f32` requires its wire type.
+        assert_eq!(decode::<f64>(&encode(&0.5f32).unwrap()).unwrap(), 0.5);
+        assert_eq!(error::<f32>(&encode(&0.5f64).unwrap()),
+                   invalid(Invalid::UnexpectedType { expected: "float32" }, WireType::Float64, 0, vec![]));
+        assert_eq!(error::<f64>(&encode(&true).unwrap()),
+                   invalid(Invalid::UnexpectedType { expected: "number" }, WireType::BoolTrue, 0, vec![]));
+    }
+
+    #[test]
+    fn borrowed() {
+        let bytes = encode("hello").unwrap();
+        let string = decode::<&str>(&bytes).unwrap();
+        assert_eq!(string, "hello");
+        assert!(bytes.as_ptr_range().contains(&string.as_ptr()));
+        round_trip(&"hello".to_string());
+
+        let bytes = encode(&b"\x00\xff"[..]).unwrap();
+        let slice = decode::<&[u8]>(&bytes).unwrap();
+        assert_eq!(slice, b"\x00\xff");
+        assert!(bytes.as_ptr_range().contains(&slice.as_ptr()));
+
+        let bytes = build(|e| {
+            e.append_byte(WireType::String as u8);
+            e.append_size_prefixed(b"a\xff");
+        });
+        let error = decode::<&str>(&bytes).unwrap_err();
+        assert_eq!(error.reason, Reason::InvalidUtf8);
+        assert_eq!(error.offset, 3);
+        let bytes = encode("a").unwrap();
+        assert_eq!(located(&bytes, decode::<&[u8]>(&bytes).unwrap_err()),
+                   invalid(Invalid::UnexpectedType { expected: "bytes" }, WireType::String, 0, vec![]));
+    }
+
+    #[test]
+    fn arrays_and_vecs() {
+        round_trip(&[1u32, 2, 3]);
+        round_trip(&[0u8; 0]);
+        round_trip(&vec![vec![1i16], vec![], vec![-1, 2]]);
+
+        let bytes = encode(&[1u32, 2, 3]).unwrap();
+        assert_eq!(error::<[u32; 2]>(&bytes),
+                   invalid(Invalid::UnexpectedLength, WireType::List, 0, vec![]));
+        assert_eq!(error::<Vec<String>>(&bytes),
+                   invalid(Invalid::UnexpectedType { expected: "string" }, WireType::Nat8, 3, vec![PathSegment::Index(0)]));
+        assert_eq!(error::<Vec<u8>>(&encode(&b"ab"[..]).unwrap()),
+                   invalid(Invalid::UnexpectedType { expected: "list" }, WireType::Bytes, 0, vec![]));
+    }
+
+    #[test]
+    fn tuples() {
+        round_trip(&(1u8,));
+        round_trip(&(1u8, -2i8));
+        round_trip(&(1u8, -2i8, "c".to_string()));
+        round_trip(&(1u8, 2u8, 3u8, 4u8));
+        round_trip(&(1u8, 2u8, 3u8, 4u8, 5u8));
+        round_trip(&(1u8, 2u8, 3u8, 4u8, 5u8, 6u8));
+        round_trip(&(1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8));
+        round_trip(&(1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8));
+        round_trip(&(1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8));
+        round_trip(&(1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8, 10u8));
+        round_trip(&(1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8, 10u8, 11u8));
+        round_trip(&(1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8, 10u8, 11u8, (12u8, true)));
+
+        let bytes = encode(&(1u8, 2u8, 3u8)).unwrap();
+        assert_eq!(error::<(u8, u8)>(&bytes),
+                   invalid(Invalid::UnexpectedLength, WireType::List, 0, vec![]));
+        assert_eq!(error::<(u8, u8, String)>(&bytes),
+                   invalid(Invalid::UnexpectedType { expected: "string" }, WireType::Nat8, 7, vec![PathSegment::Index(2)]));
+    }
+
+    #[test]
+    fn list_past_the_end() {
+        let bytes = encode(&[1u8]).unwrap();
+        let value = decoder::decode_document(&mut Reader::new(&bytes)).unwrap();
+        let mut list = List::new(&value).unwrap();
+        assert_eq!(u8::decode(&list.element().unwrap()).unwrap(), 1);
+        let error = list.element().err().unwrap();
+        assert_eq!((error.expected, error.available), (2, 1));
+        assert_eq!(located(&bytes, error), invalid(Invalid::UnexpectedLength, WireType::List, 0, vec![]));
+    }
+
+    #[test]
+    fn maps() {
+        let map: HashMap<String, u32> = [("a".to_string(), 1), ("b".to_string(), 2)].into_iter().collect();
+        round_trip(&map);
+        round_trip(&BTreeMap::<Vec<u8>, Vec<bool>>::new());
+        round_trip(&[(b"x".to_vec(), vec![true]), (b"y".to_vec(), vec![])].into_iter().collect::<BTreeMap<_, _>>());
+
+        let bytes = encode(&map).unwrap();
+        let borrowed = decode::<BTreeMap<&str, u8>>(&bytes).unwrap();
+        assert_eq!(borrowed.into_iter().collect::<Vec<_>>(), vec![("a", 1), ("b", 2)]);
+
+        let bytes = build(|e| { e.value().tag(b"a", |e| e.write_unsigned(300)).payload_null(); });
+        assert_eq!(error::<BTreeMap<String, u8>>(&bytes),
+                   invalid(Invalid::OutOfRange, WireType::Nat16, 5, vec![PathSegment::Tag(b"a".to_vec())]));
+
+        let bytes = build(|e| { e.value().tag(b"\xff", |e| e.write_null()).payload_null(); });
+        assert_eq!(error::<HashMap<String, ()>>(&bytes),
+                   invalid(Invalid::InvalidSymbol, WireType::Null, 0, vec![PathSegment::Tag(b"\xff".to_vec())]));
+        assert_eq!(error::<HashMap<String, ()>>(&encode(&()).unwrap()),
+                   invalid(Invalid::UnexpectedType { expected: "null with tags" }, WireType::Null, 0, vec![]));
+    }
+
+    #[test]
+    fn pointers() {
+        round_trip(&Box::new(1u8));
+        round_trip(&Rc::new("rc".to_string()));
+        round_trip(&Arc::new(vec![Box::new(-1i32)]));
+        assert_eq!(encode(&Box::<str>::from("s")).unwrap(), encode("s").unwrap());
+
+        assert_eq!(error::<Box<u8>>(&encode("x").unwrap()),
+                   invalid(Invalid::UnexpectedType { expected: "integer" }, WireType::String, 0, vec![]));
+    }
+
+    #[test]
+    fn options() {
+        round_trip(&Some(5u8));
+        round_trip(&None::<u8>);
+        round_trip(&vec![Some("a".to_string()), None]);
+
+        // a `Null` with tags is a value, not `None`.
+        let bytes = build(|e| { e.value().with_tags().payload_null(); });
+        assert_eq!(decode::<Option<BTreeMap<String, u8>>>(&bytes).unwrap(), Some(BTreeMap::new()));
+
+        // ambiguous, see `Option`.
+        assert_eq!(decode::<Option<()>>(&encode(&Some(())).unwrap()).unwrap(), None);
+        assert_eq!(decode::<Option<Option<u8>>>(&encode(&Some(None::<u8>)).unwrap()).unwrap(), None);
+
+        assert_eq!(error::<Option<u8>>(&encode("x").unwrap()),
+                   invalid(Invalid::UnexpectedType { expected: "integer" }, WireType::String, 0, vec![]));
+    }
+
+    #[test]
+    fn error_offsets() {
+        let bytes = encode(&[1u8, 2]).unwrap();
+        let length = decode::<(u8,)>(&bytes).unwrap_err();
+        assert_eq!((length.stage, length.expected, length.available), (Stage::Payload, 1, 2));
+        assert_eq!(length.to_string(), "InvalidValue(UnexpectedLength) at offset 0 while decoding Payload of List: expected 1, available 2");
+
+        // errors locate the value that failed, not its parent.
+        let mut map = BTreeMap::new();
+        map.insert("a", vec![vec![1u8], vec![2, 3]]);
+        let bytes = encode(&map).unwrap();
+        let (_, _, offset, path) = error::<BTreeMap<String, Vec<(u8,)>>>(&bytes);
+        assert_eq!(path, Path(vec![PathSegment::Tag(b"a".to_vec()), PathSegment::Index(1)]));
+        let element = encode(&[2u8, 3]).unwrap();
+        assert_eq!(bytes[offset..offset + element.len()], element);
+
+        let bytes = build(|e| { e.value().tag(b"a", |e| e.write_null()).payload_null(); });
+        let error = decode::<BTreeMap<String, u8>>(&bytes).unwrap_err();
+        assert_eq!(error.stage, Stage::Payload);
+        let null = bytes.len() - 1;
+        assert_eq!(located(&bytes, error), invalid(Invalid::UnexpectedType { expected: "integer" }, WireType::Null, null, vec![PathSegment::Tag(b"a".to_vec())]));
+
+        // kinds and missing tags are reported on the value that lacks them.
+        let bytes = encode(&()).unwrap();
+        let value = decoder::decode_document(&mut Reader::new(&bytes)).unwrap();
+        let error = DecodeError::unexpected_kind(&value);
+        assert_eq!((error.stage, error.reason), (Stage::Kind, Reason::InvalidValue(Invalid::UnexpectedKind)));
+        let error = DecodeError::missing_tag(&value, b"t");
+        assert_eq!(error.stage, Stage::Tags);
+        assert_eq!(located(&bytes, error), invalid(Invalid::MissingTag, WireType::Null, 0, vec![PathSegment::Tag(b"t".to_vec())]));
+    }
+}
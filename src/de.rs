@@ -0,0 +1,656 @@
+use serde::de::{self, Deserialize, DeserializeSeed, Visitor, Unexpected};
+use serde::de::value::{BorrowedStrDeserializer, BorrowedBytesDeserializer};
+use slice_reader::Reader;
+use crate::wire_type::WireType;
+use crate::utils::offset_in;
+use crate::decoder::{self, DecodeError, Stage, Reason, Path, PathSegment, Payload, Value, DecodeOptions, ListDecoder, TagDecoder};
+
+
+// the inverse of the mapping in `ser`. strings, bytes and symbols are
+// borrowed from the input buffer.
+// enums are read from the kind symbol. values without a kind, but with a
+// string or symbol payload, are accepted as unit variants.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Message (String, Path),
+    Decode  (DecodeError),
+}
+
+impl Error {
+    fn in_index(self, index: usize) -> Error {
+        match self {
+            Error::Message (message, mut path) => {
+                path.0.insert(0, PathSegment::Index(index));
+                Error::Message(message, path)
+            },
+            Error::Decode (error) => Error::Decode(error.in_index(index)),
+        }
+    }
+
+    fn in_tag(self, symbol: &[u8]) -> Error {
+        match self {
+            Error::Message (message, mut path) => {
+                path.0.insert(0, PathSegment::Tag(symbol.to_vec()));
+                Error::Message(message, path)
+            },
+            Error::Decode (error) => Error::Decode(error.in_tag(symbol)),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Message (message, path) => {
+                write!(f, "{}", message)?;
+                if !path.0.is_empty() {
+                    write!(f, " at {}", path)?;
+                }
+                Ok(())
+            },
+            Error::Decode (error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(message: T) -> Error {
+        Error::Message(message.to_string(), Path::default())
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Error {
+        Error::Decode(error)
+    }
+}
+
+
+pub fn from_slice<'de, T: Deserialize<'de>>(buffer: &'de [u8]) -> Result<T, Error> {
+    from_slice_with_options(buffer, &DecodeOptions::new())
+}
+
+pub fn from_slice_with_options<'de, T: Deserialize<'de>>(buffer: &'de [u8], options: &DecodeOptions<'de>) -> Result<T, Error> {
+    let mut reader = Reader::new(buffer);
+    let value = decoder::decode_document_with_options(&mut reader, options)?;
+    let result = T::deserialize(Deserializer::new(value))?;
+    if reader.has_some() {
+        return Err(DecodeError::new(Stage::Trailing, Reason::TrailingBytes, &reader).into());
+    }
+    Ok(result)
+}
+
+
+// error offsets are relative to the root of the document `value` was
+// decoded from.
+pub struct Deserializer<'de> {
+    value: Value<'de>,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(value: Value<'de>) -> Deserializer<'de> {
+        Deserializer { value }
+    }
+
+    fn is_map(&self) -> bool {
+        self.value.header.has_tags && matches!(self.value.payload, Payload::Null)
+    }
+
+    fn unexpected(&self) -> Unexpected<'de> {
+        if self.is_map() {
+            return Unexpected::Map;
+        }
+
+        match self.value.payload {
+            Payload::Null              => Unexpected::Unit,
+            Payload::Bool      (value) => Unexpected::Bool(value),
+            Payload::Nat8      (value) => Unexpected::Unsigned(value as u64),
+            Payload::Nat16     (value) => Unexpected::Unsigned(value as u64),
+            Payload::Nat32     (value) => Unexpected::Unsigned(value as u64),
+            Payload::Nat64     (value) => Unexpected::Unsigned(value),
+            Payload::Int8      (value) => Unexpected::Signed(value as i64),
+            Payload::Int16     (value) => Unexpected::Signed(value as i64),
+            Payload::Int32     (value) => Unexpected::Signed(value as i64),
+            Payload::Int64     (value) => Unexpected::Signed(value),
+            Payload::Float32   (value) => Unexpected::Float(value as f64),
+            Payload::Float64   (value) => Unexpected::Float(value),
+            Payload::Nat       (_)     => Unexpected::Other("nat"),
+            Payload::Int       (_)     => Unexpected::Other("int"),
+            Payload::Decimal32 (_)     => Unexpected::Other("decimal32"),
+            Payload::Decimal64 (_)     => Unexpected::Other("decimal64"),
+            Payload::Bytes     (value) => Unexpected::Bytes(value),
+            Payload::String    (_)     => Unexpected::Other("string"),
+            Payload::Symbol    (_)     => Unexpected::Other("symbol"),
+            Payload::List      (_)     => Unexpected::Seq,
+        }
+    }
+
+    fn string(&self, string: &'de [u8]) -> Result<&'de str, Error> {
+        std::str::from_utf8(string).map_err(|e| {
+            let base = offset_in(self.value.document.root, string);
+            DecodeError::invalid_utf8(self.value.header, string, e).rebase(base).into()
+        })
+    }
+
+    fn visit_list<V: Visitor<'de>>(self, list: &'de [u8], visitor: V) -> Result<V::Value, Error> {
+        let mut access = ListAccess::new(list, &self.value)?;
+        let result = visitor.visit_seq(&mut access)?;
+        access.finish()?;
+        Ok(result)
+    }
+
+    fn visit_tags<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut access = TagAccess::new(&self.value)?;
+        let result = visitor.visit_map(&mut access)?;
+        access.finish()?;
+        Ok(result)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_map() {
+            return self.visit_tags(visitor);
+        }
+
+        match self.value.payload {
+            Payload::Null              => visitor.visit_unit(),
+            Payload::Bool      (value) => visitor.visit_bool(value),
+            Payload::Nat8      (value) => visitor.visit_u8(value),
+            Payload::Nat16     (value) => visitor.visit_u16(value),
+            Payload::Nat32     (value) => visitor.visit_u32(value),
+            Payload::Nat64     (value) => visitor.visit_u64(value),
+            Payload::Int8      (value) => visitor.visit_i8(value),
+            Payload::Int16     (value) => visitor.visit_i16(value),
+            Payload::Int32     (value) => visitor.visit_i32(value),
+            Payload::Int64     (value) => visitor.visit_i64(value),
+            Payload::Float32   (value) => visitor.visit_f32(value),
+            Payload::Float64   (value) => visitor.visit_f64(value),
+            Payload::Bytes     (value) => visitor.visit_borrowed_bytes(value),
+
+            Payload::Nat (_) => {
+                visitor.visit_u128(self.value.as_u128().map_err(<Error as de::Error>::custom)?)
+            },
+
+            Payload::Int (_) => {
+                visitor.visit_i128(self.value.as_i128().map_err(<Error as de::Error>::custom)?)
+            },
+
+            Payload::Decimal32 (_) | Payload::Decimal64 (_) => {
+                Err(de::Error::invalid_type(self.unexpected(), &visitor))
+            },
+
+            Payload::String (string) => {
+                visitor.visit_borrowed_str(self.string(string)?)
+            },
+
+            Payload::Symbol (symbol) => {
+                match std::str::from_utf8(symbol) {
+                    Ok(symbol) => visitor.visit_borrowed_str(symbol),
+                    Err(_)     => visitor.visit_borrowed_bytes(symbol),
+                }
+            },
+
+            Payload::List (list) => self.visit_list(list, visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let header = self.value.header;
+        if header.wire_type == WireType::Null && !header.has_kind && !header.has_tags {
+            visitor.visit_none()
+        }
+        else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_map() {
+            self.visit_tags(visitor)
+        }
+        else {
+            Err(de::Error::invalid_type(self.unexpected(), &visitor))
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        if self.value.header.has_kind {
+            let variant = self.value.kind;
+            return visitor.visit_enum(EnumAccess { variant, value: Some(self) });
+        }
+
+        match self.value.payload {
+            Payload::String (variant) => {
+                self.string(variant)?;
+                visitor.visit_enum(EnumAccess { variant, value: None })
+            },
+            Payload::Symbol (variant) => {
+                visitor.visit_enum(EnumAccess { variant, value: None })
+            },
+            _ => Err(de::Error::invalid_type(self.unexpected(), &visitor)),
+        }
+    }
+
+    // skipped values are walked, so nested tags and lists must be well
+    // formed. their strings are not checked for UTF-8.
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        for value in decoder::Walker::new(self.value) {
+            value?;
+        }
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct identifier
+    }
+}
+
+
+fn deserialize_symbol<'de, S: DeserializeSeed<'de>>(seed: S, symbol: &'de [u8]) -> Result<S::Value, Error> {
+    match std::str::from_utf8(symbol) {
+        Ok(symbol) => seed.deserialize(BorrowedStrDeserializer::new(symbol)),
+        Err(_)     => seed.deserialize(BorrowedBytesDeserializer::new(symbol)),
+    }
+}
+
+
+struct ListAccess<'de> {
+    length: usize,
+    list:   ListDecoder<'de>,
+}
+
+impl<'de> ListAccess<'de> {
+    fn new(list: &'de [u8], value: &Value<'de>) -> Result<ListAccess<'de>, Error> {
+        let list = ListDecoder::new(list, value.document, value.depth)?;
+        Ok(ListAccess { length: list.remaining, list })
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if self.list.remaining != 0 {
+            return Err(de::Error::invalid_length(self.length, &"fewer elements in list"));
+        }
+        self.list.check_error()?;
+        Ok(())
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for ListAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>, Error> {
+        let index = self.length - self.list.remaining;
+        match self.list.next() {
+            Some(value) => {
+                seed.deserialize(Deserializer::new(value))
+                    .map(Some).map_err(|e| e.in_index(index))
+            },
+            None => {
+                match self.list.take_element_error() {
+                    Some(e) => Err(Error::Decode(e)),
+                    None    => Ok(None),
+                }
+            },
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.list.remaining)
+    }
+}
+
+
+struct TagAccess<'de> {
+    tags:  TagDecoder<'de>,
+    value: Option<(&'de [u8], Value<'de>)>,
+}
+
+impl<'de> TagAccess<'de> {
+    fn new(value: &Value<'de>) -> Result<TagAccess<'de>, Error> {
+        Ok(TagAccess { tags: value.tags()?, value: None })
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if self.tags.remaining != 0 {
+            return Err(de::Error::invalid_length(self.tags.remaining, &"fewer tags"));
+        }
+        self.tags.check_error()?;
+        Ok(())
+    }
+}
+
+impl<'de> de::MapAccess<'de> for TagAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>, Error> {
+        match self.tags.next() {
+            Some((symbol, value)) => {
+                self.value = Some((symbol, value));
+                deserialize_symbol(seed, symbol).map(Some).map_err(|e| e.in_tag(symbol))
+            },
+            None => {
+                match self.tags.error.take() {
+                    Some(e) => Err(Error::Decode(e)),
+                    None    => Ok(None),
+                }
+            },
+        }
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value, Error> {
+        let (symbol, value) = self.value.take()
+            .ok_or_else(|| <Error as de::Error>::custom("value without key"))?;
+        seed.deserialize(Deserializer::new(value)).map_err(|e| e.in_tag(symbol))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.tags.remaining)
+    }
+}
+
+
+// `value` is `None` for unit variants that were written as a string or
+// symbol payload.
+struct EnumAccess<'de> {
+    variant: &'de [u8],
+    value:   Option<Deserializer<'de>>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = VariantAccess<'de>;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, VariantAccess<'de>), Error> {
+        let variant = deserialize_symbol(seed, self.variant)?;
+        Ok((variant, VariantAccess { value: self.value }))
+    }
+}
+
+struct VariantAccess<'de> {
+    value: Option<Deserializer<'de>>,
+}
+
+impl<'de> VariantAccess<'de> {
+    fn value(self, expected: &dyn de::Expected) -> Result<Deserializer<'de>, Error> {
+        self.value.ok_or_else(|| de::Error::invalid_type(Unexpected::UnitVariant, expected))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.value {
+            Some(value) => {
+                if value.value.header.has_tags || !matches!(value.value.payload, Payload::Null) {
+                    return Err(de::Error::invalid_type(value.unexpected(), &"unit variant"));
+                }
+                Ok(())
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, Error> {
+        let value = self.value(&"newtype variant")?;
+        match value.value.payload {
+            Payload::List (list) if !value.value.header.has_tags => {
+                let mut access = ListAccess::new(list, &value.value)?;
+                if access.length != 1 {
+                    return Err(de::Error::invalid_length(access.length, &"newtype variant"));
+                }
+                let result = de::SeqAccess::next_element_seed(&mut access, seed)?;
+                access.finish()?;
+                Ok(result.unwrap())
+            },
+            _ => Err(de::Error::invalid_type(value.unexpected(), &"newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _length: usize, visitor: V) -> Result<V::Value, Error> {
+        let value = self.value(&visitor)?;
+        match value.value.payload {
+            Payload::List (list) if !value.value.header.has_tags => value.visit_list(list, visitor),
+            _ => Err(de::Error::invalid_type(value.unexpected(), &visitor)),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        let value = self.value(&visitor)?;
+        if value.is_map() {
+            value.visit_tags(visitor)
+        }
+        else {
+            Err(de::Error::invalid_type(value.unexpected(), &visitor))
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Serialize, Deserialize};
+    use crate::encoder::Encoder;
+    use crate::ser::to_vec;
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de> + PartialEq + std::fmt::Debug>(value: &T) {
+        let bytes = to_vec(value).unwrap();
+        assert_eq!(&from_slice::<T>(&bytes).unwrap(), value);
+    }
+
+    fn build<F: FnOnce(&mut Encoder)>(f: F) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        f(&mut encoder);
+        encoder.build().unwrap()
+    }
+
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name:     String,
+        id:       u64,
+        big:      u128,
+        location: Point,
+        tags:     Vec<String>,
+        nickname: Option<String>,
+        ratio:    f32,
+        unit:     (),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Meters(f64);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rectangle(f64, f64),
+        Polygon { points: Vec<Point>, closed: bool },
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Borrowed<'a> {
+        name:  &'a str,
+        bytes: &'a [u8],
+        kind:  &'a str,
+    }
+
+
+    #[test]
+    fn structs() {
+        round_trip(&Point { x: 1, y: -2 });
+        round_trip(&User {
+            name:     "ann".into(),
+            id:       u64::MAX,
+            big:      u128::MAX,
+            location: Point { x: 3, y: 4 },
+            tags:     vec!["a".into(), "b".into()],
+            nickname: None,
+            ratio:    0.5,
+            unit:     (),
+        });
+        round_trip(&Meters(2.5));
+        round_trip(&(1u8, "x".to_string(), vec![Some(1i8), None]));
+
+        // tags are matched by symbol, not by position.
+        let bytes = build(|e| {
+            e.value().with_tags()
+                .tag(b"y", |e| e.write_signed(2))
+                .tag(b"x", |e| e.write_signed(1))
+                .payload_null();
+        });
+        assert_eq!(from_slice::<Point>(&bytes).unwrap(), Point { x: 1, y: 2 });
+    }
+
+    #[test]
+    fn enum_variants() {
+        round_trip(&Shape::Empty);
+        round_trip(&Shape::Circle(1.0));
+        round_trip(&Shape::Rectangle(1.0, 2.0));
+        round_trip(&Shape::Polygon { points: vec![Point { x: 0, y: 0 }], closed: true });
+        round_trip(&vec![Shape::Empty, Shape::Circle(-1.0)]);
+
+        let bytes = build(|e| { e.value().kind(b"Circle").payload_list(2, |e| { e.write_f64(1.0); e.write_f64(2.0); }); });
+        assert!(from_slice::<Shape>(&bytes).is_err());
+
+        let bytes = build(|e| { e.value().kind(b"Empty").payload_list(0, |_| ()); });
+        assert!(from_slice::<Shape>(&bytes).is_err());
+    }
+
+    #[test]
+    fn unit_variants_as_strings() {
+        assert_eq!(from_slice::<Shape>(&build(|e| e.write_string("Empty"))).unwrap(), Shape::Empty);
+        assert_eq!(from_slice::<Shape>(&build(|e| e.write_symbol(b"Empty"))).unwrap(), Shape::Empty);
+
+        // only unit variants can be written without a kind.
+        assert!(from_slice::<Shape>(&build(|e| e.write_string("Circle"))).is_err());
+        assert!(from_slice::<Shape>(&build(|e| e.write_string("Square"))).is_err());
+        assert!(from_slice::<Shape>(&build(|e| e.write_f64(1.0))).is_err());
+    }
+
+    #[test]
+    fn borrowed() {
+        let bytes = build(|e| {
+            e.value().with_tags()
+                .tag(b"name",  |e| e.write_string("name"))
+                .tag(b"bytes", |e| e.write_bytes(b"\x00\xff"))
+                .tag(b"kind",  |e| e.write_symbol(b"symbol"))
+                .payload_null();
+        });
+        let value = from_slice::<Borrowed>(&bytes).unwrap();
+        assert_eq!(value, Borrowed { name: "name", bytes: b"\x00\xff", kind: "symbol" });
+
+        let range = bytes.as_ptr_range();
+        assert!(range.contains(&value.name.as_ptr()));
+        assert!(range.contains(&value.bytes.as_ptr()));
+        assert!(range.contains(&value.kind.as_ptr()));
+    }
+
+    #[test]
+    fn error_paths() {
+        let bytes = build(|e| {
+            e.value().payload_list(2, |e| {
+                e.value().with_tags().tag(b"x", |e| e.write_signed(1)).tag(b"y", |e| e.write_signed(2)).payload_null();
+                e.value().with_tags().tag(b"x", |e| e.write_signed(1)).tag(b"y", |e| e.write_string("2")).payload_null();
+            });
+        });
+        let error = from_slice::<Vec<Point>>(&bytes).unwrap_err();
+        match &error {
+            Error::Message (message, path) => {
+                assert!(message.starts_with("invalid type"), "{}", message);
+                assert_eq!(path, &Path(vec![PathSegment::Index(1), PathSegment::Tag(b"y".to_vec())]));
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+        assert!(error.to_string().ends_with(" at $[1].y"), "{}", error);
+
+        let bytes = build(|e| { e.value().with_tags().tag(b"x", |e| e.write_signed(1)).payload_null(); });
+        match from_slice::<Point>(&bytes).unwrap_err() {
+            Error::Message (message, path) => {
+                assert_eq!(message, "missing field `y`");
+                assert_eq!(path, Path::default());
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn ignored_values_are_walked() {
+        let bytes = build(|e| {
+            e.value().with_tags()
+                .tag(b"x", |e| e.write_signed(1))
+                .tag(b"y", |e| e.write_signed(2))
+                .tag(b"z", |e| e.write_null())
+                .payload_null();
+        });
+        assert_eq!(from_slice::<Point>(&bytes).unwrap(), Point { x: 1, y: 2 });
+
+        // a list of length 2, with one element.
+        let bytes = build(|e| {
+            e.value().with_tags()
+                .tag(b"x", |e| e.write_signed(1))
+                .tag(b"y", |e| e.write_signed(2))
+                .tag(b"z", |e| {
+                    e.append_byte(WireType::List as u8);
+                    e.append_size_prefixed(&[2 << 2, WireType::Nat8 as u8, 1]);
+                })
+                .payload_null();
+        });
+        match from_slice::<Point>(&bytes).unwrap_err() {
+            Error::Decode (error) => {
+                assert_eq!(error.reason, Reason::UnexpectedEnd);
+                assert_eq!(error.path, Path(vec![PathSegment::Tag(b"z".to_vec()), PathSegment::Index(1)]));
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_offsets() {
+        let mut bytes = build(|e| {
+            e.value().payload_list(2, |e| {
+                e.value().payload_list(1, |e| e.write_string("a"));
+                e.value().payload_list(2, |e| { e.write_string("b"); e.write_string("ok\u{1}XYZ"); });
+            });
+        });
+        let offset = bytes.windows(3).position(|window| window == b"XYZ").unwrap();
+        bytes[offset] = 0xff;
+
+        let error = from_slice::<Vec<Vec<String>>>(&bytes).unwrap_err();
+        match error {
+            Error::Decode (error) => {
+                assert_eq!(error.reason, Reason::InvalidUtf8);
+                assert_eq!(error.offset, offset);
+                assert_eq!(error.wire_type, Some(WireType::String));
+                assert_eq!(error.path, Path(vec![PathSegment::Index(1), PathSegment::Index(1)]));
+            },
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+}
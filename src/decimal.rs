@@ -0,0 +1,546 @@
+use std::cmp::Ordering;
+
+
+// ieee 754-2008 decimal floating point numbers in the binary integer decimal
+// (bid) encoding. the value is `coefficient * 10^exponent`.
+//
+// operations are exact: parsing and conversions fail instead of rounding.
+// comparison is by numeric value, so `1.0 == 1.00`, even though the two
+// are encoded differently.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecimalError {
+    Invalid,
+    Inexact,
+    NotFinite,
+}
+
+impl std::fmt::Display for DecimalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecimalError::Invalid   => write!(f, "invalid decimal literal"),
+            DecimalError::Inexact   => write!(f, "decimal can't be represented exactly"),
+            DecimalError::NotFinite => write!(f, "decimal is not finite"),
+        }
+    }
+}
+
+impl std::error::Error for DecimalError {}
+
+
+enum Unpacked {
+    Finite   { negative: bool, coefficient: u64, exponent: i32 },
+    Infinity { negative: bool },
+    NaN,
+}
+
+fn digits(value: u64) -> i32 {
+    if value == 0 { 1 } else { value.ilog10() as i32 + 1 }
+}
+
+// `a` and `b` are non-zero.
+fn compare_magnitude(a: u64, a_exponent: i32, b: u64, b_exponent: i32) -> Ordering {
+    let a_adjusted = a_exponent + digits(a) - 1;
+    let b_adjusted = b_exponent + digits(b) - 1;
+    if a_adjusted != b_adjusted {
+        return a_adjusted.cmp(&b_adjusted);
+    }
+
+    // note: the adjusted exponents are equal, so the exponents differ by
+    // less than the precision, and the scaled coefficient fits.
+    if a_exponent >= b_exponent {
+        (a as u128 * 10u128.pow((a_exponent - b_exponent) as u32)).cmp(&(b as u128))
+    }
+    else {
+        (a as u128).cmp(&(b as u128 * 10u128.pow((b_exponent - a_exponent) as u32)))
+    }
+}
+
+fn compare(a: Unpacked, b: Unpacked) -> Option<Ordering> {
+    // note: zeros and infinities are ordered by their rank alone.
+    fn rank(value: &Unpacked) -> Option<i32> {
+        Some(match *value {
+            Unpacked::Finite   { coefficient: 0, .. } => 0,
+            Unpacked::Finite   { negative, .. }       => if negative { -1 } else { 1 },
+            Unpacked::Infinity { negative }           => if negative { -2 } else { 2 },
+            Unpacked::NaN                             => return None,
+        })
+    }
+
+    let (a_rank, b_rank) = (rank(&a)?, rank(&b)?);
+    if a_rank != b_rank || a_rank.abs() != 1 {
+        return Some(a_rank.cmp(&b_rank));
+    }
+
+    match (a, b) {
+        (Unpacked::Finite { coefficient: a, exponent: a_exponent, .. }, Unpacked::Finite { coefficient: b, exponent: b_exponent, .. }) => {
+            let result = compare_magnitude(a, a_exponent, b, b_exponent);
+            Some(if a_rank < 0 { result.reverse() } else { result })
+        },
+        _ => unreachable!(),
+    }
+}
+
+// scales `coefficient * 10^exponent` into range, without losing digits.
+fn fit(coefficient: u128, exponent: i64, max_coefficient: u128, min_exponent: i32, max_exponent: i32) -> Option<(u128, i32)> {
+    let (mut coefficient, mut exponent) = (coefficient, exponent);
+
+    while coefficient > max_coefficient {
+        if coefficient % 10 != 0 {
+            return None;
+        }
+        coefficient /= 10;
+        exponent    += 1;
+    }
+
+    if coefficient == 0 {
+        exponent = exponent.clamp(min_exponent as i64, max_exponent as i64);
+    }
+
+    while exponent > max_exponent as i64 {
+        if coefficient * 10 > max_coefficient {
+            return None;
+        }
+        coefficient *= 10;
+        exponent    -= 1;
+    }
+
+    while exponent < min_exponent as i64 {
+        if coefficient % 10 != 0 {
+            return None;
+        }
+        coefficient /= 10;
+        exponent    += 1;
+    }
+
+    Some((coefficient, exponent as i32))
+}
+
+// `[+-](digits[.digits]|.digits)[e[+-]digits]`, `inf`, `infinity` or `nan`.
+fn parse(string: &str, max_coefficient: u64, min_exponent: i32, max_exponent: i32) -> Result<Unpacked, DecimalError> {
+    let (negative, rest) = match string.as_bytes().first() {
+        Some(b'-') => (true,  &string[1..]),
+        Some(b'+') => (false, &string[1..]),
+        _          => (false, string),
+    };
+
+    if rest.eq_ignore_ascii_case("inf") || rest.eq_ignore_ascii_case("infinity") {
+        return Ok(Unpacked::Infinity { negative });
+    }
+    if rest.eq_ignore_ascii_case("nan") {
+        return Ok(Unpacked::NaN);
+    }
+
+    let (mantissa, exponent) = match rest.find(['e', 'E']) {
+        Some(index) => (&rest[..index], Some(&rest[index + 1..])),
+        None        => (rest, None),
+    };
+
+    let mut coefficient = 0u128;
+    let mut scale       = 0i64;
+    let mut any_digits  = false;
+    let mut after_point = false;
+    for c in mantissa.bytes() {
+        match c {
+            b'0'..=b'9' => {
+                any_digits = true;
+                let digit = (c - b'0') as u128;
+                if coefficient < 10u128.pow(37) {
+                    coefficient = coefficient * 10 + digit;
+                    if after_point { scale -= 1 }
+                }
+                else if digit != 0 {
+                    return Err(DecimalError::Inexact);
+                }
+                else if !after_point {
+                    // note: a trailing zero that doesn't fit.
+                    scale += 1;
+                }
+            },
+            b'.' if !after_point => after_point = true,
+            _ => return Err(DecimalError::Invalid),
+        }
+    }
+    if !any_digits {
+        return Err(DecimalError::Invalid);
+    }
+
+    if let Some(exponent) = exponent {
+        let (negative, digits) = match exponent.as_bytes().first() {
+            Some(b'-') => (true,  &exponent[1..]),
+            Some(b'+') => (false, &exponent[1..]),
+            _          => (false, exponent),
+        };
+        if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(DecimalError::Invalid);
+        }
+
+        // note: saturates, values this large are out of range anyway.
+        let mut value = 0i64;
+        for c in digits.bytes() {
+            value = (value * 10 + (c - b'0') as i64).min(1 << 32);
+        }
+        scale += if negative { -value } else { value };
+    }
+
+    let (coefficient, exponent) = fit(coefficient, scale, max_coefficient as u128, min_exponent, max_exponent)
+        .ok_or(DecimalError::Inexact)?;
+    Ok(Unpacked::Finite { negative, coefficient: coefficient as u64, exponent })
+}
+
+
+// the general decimal arithmetic to-scientific-string conversion.
+fn format(f: &mut std::fmt::Formatter, value: Unpacked) -> std::fmt::Result {
+    match value {
+        Unpacked::NaN                   => write!(f, "NaN"),
+        Unpacked::Infinity { negative } => write!(f, "{}Infinity", if negative { "-" } else { "" }),
+
+        Unpacked::Finite { negative, coefficient, exponent } => {
+            if negative {
+                write!(f, "-")?;
+            }
+
+            let digits   = coefficient.to_string();
+            let adjusted = exponent as i64 + digits.len() as i64 - 1;
+
+            if exponent <= 0 && adjusted >= -6 {
+                let point = digits.len() as i64 + exponent as i64;
+                if exponent == 0 {
+                    write!(f, "{}", digits)
+                }
+                else if point > 0 {
+                    let (integer, fraction) = digits.split_at(point as usize);
+                    write!(f, "{}.{}", integer, fraction)
+                }
+                else {
+                    write!(f, "0.{}{}", "0".repeat(-point as usize), digits)
+                }
+            }
+            else {
+                let (first, rest) = digits.split_at(1);
+                write!(f, "{}", first)?;
+                if !rest.is_empty() {
+                    write!(f, ".{}", rest)?;
+                }
+                write!(f, "E{}{}", if adjusted < 0 { "-" } else { "+" }, adjusted.abs())
+            }
+        },
+    }
+}
+
+
+macro_rules! decimal {
+    ($name: ident, $bits: ty, $width: expr, $exponent_bits: expr, $bias: expr, $max_coefficient: expr) => {
+        #[derive(Clone, Copy)]
+        pub struct $name($bits);
+
+        impl $name {
+            const COEFFICIENT_BITS: u32 = $width - 1 - $exponent_bits;
+            const MAX_BIASED:       i32 = 3 << ($exponent_bits - 2);
+
+            pub const MAX_COEFFICIENT: u64 = $max_coefficient;
+            pub const MIN_EXPONENT:    i32 = -$bias;
+            pub const MAX_EXPONENT:    i32 = Self::MAX_BIASED - 1 - $bias;
+
+            pub const ZERO:         $name = $name(($bias as $bits) << Self::COEFFICIENT_BITS);
+            pub const INFINITY:     $name = $name(0b11110 << ($width - 6));
+            pub const NEG_INFINITY: $name = $name(0b111110 << ($width - 6));
+            pub const NAN:          $name = $name(0b11111 << ($width - 6));
+
+            pub const fn from_bits(bits: $bits) -> $name {
+                $name(bits)
+            }
+
+            pub const fn to_bits(self) -> $bits {
+                self.0
+            }
+
+            // `None` if the value can't be represented exactly.
+            pub fn from_parts(negative: bool, coefficient: u128, exponent: i32) -> Option<$name> {
+                let (coefficient, exponent) = fit(coefficient, exponent as i64,
+                    Self::MAX_COEFFICIENT as u128, Self::MIN_EXPONENT, Self::MAX_EXPONENT)?;
+
+                let sign     = (negative as $bits) << ($width - 1);
+                let biased   = (exponent + $bias) as $bits;
+                let small    = 1 << Self::COEFFICIENT_BITS;
+                let bits =
+                    if coefficient < small {
+                        sign | biased << Self::COEFFICIENT_BITS | coefficient as $bits
+                    }
+                    else {
+                        let low = coefficient as $bits & ((1 << (Self::COEFFICIENT_BITS - 2)) - 1);
+                        sign | 0b11 << ($width - 3) | biased << (Self::COEFFICIENT_BITS - 2) | low
+                    };
+                Some($name(bits))
+            }
+
+            // `(negative, coefficient, exponent)` of finite values.
+            pub fn to_parts(self) -> Option<(bool, u64, i32)> {
+                match self.unpack() {
+                    Unpacked::Finite { negative, coefficient, exponent } => Some((negative, coefficient, exponent)),
+                    _ => None,
+                }
+            }
+
+            pub fn is_nan(self) -> bool {
+                matches!(self.unpack(), Unpacked::NaN)
+            }
+
+            pub fn is_infinite(self) -> bool {
+                matches!(self.unpack(), Unpacked::Infinity { .. })
+            }
+
+            pub fn is_finite(self) -> bool {
+                matches!(self.unpack(), Unpacked::Finite { .. })
+            }
+
+            pub fn is_sign_negative(self) -> bool {
+                self.0 >> ($width - 1) != 0
+            }
+
+            fn unpack(self) -> Unpacked {
+                let bits     = self.0;
+                let negative = self.is_sign_negative();
+                let mask     = (1 << $exponent_bits) - 1;
+
+                match (bits >> ($width - 6)) & 0b11111 {
+                    0b11111 => return Unpacked::NaN,
+                    0b11110 => return Unpacked::Infinity { negative },
+                    _ => (),
+                }
+
+                let (biased, coefficient) =
+                    if (bits >> ($width - 3)) & 0b11 != 0b11 {
+                        let coefficient = bits & ((1 << Self::COEFFICIENT_BITS) - 1);
+                        ((bits >> Self::COEFFICIENT_BITS) & mask, coefficient as u64)
+                    }
+                    else {
+                        let low = bits & ((1 << (Self::COEFFICIENT_BITS - 2)) - 1);
+                        let coefficient = (0b100 << (Self::COEFFICIENT_BITS - 2)) | low;
+                        ((bits >> (Self::COEFFICIENT_BITS - 2)) & mask, coefficient as u64)
+                    };
+
+                // note: non-canonical coefficients are zero.
+                let coefficient = if coefficient > Self::MAX_COEFFICIENT { 0 } else { coefficient };
+                Unpacked::Finite { negative, coefficient, exponent: biased as i32 - $bias }
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> $name {
+                $name::ZERO
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = DecimalError;
+
+            fn from_str(string: &str) -> Result<$name, DecimalError> {
+                match parse(string, Self::MAX_COEFFICIENT, Self::MIN_EXPONENT, Self::MAX_EXPONENT)? {
+                    Unpacked::NaN                   => Ok($name::NAN),
+                    Unpacked::Infinity { negative } => Ok(if negative { $name::NEG_INFINITY } else { $name::INFINITY }),
+                    Unpacked::Finite { negative, coefficient, exponent } => {
+                        $name::from_parts(negative, coefficient as u128, exponent).ok_or(DecimalError::Inexact)
+                    },
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                format(f, self.unpack())
+            }
+        }
+
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "{}({})", stringify!($name), self)
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                self.partial_cmp(other) == Some(Ordering::Equal)
+            }
+        }
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &$name) -> Option<Ordering> {
+                compare(self.unpack(), other.unpack())
+            }
+        }
+    };
+}
+
+decimal!(Decimal32, u32, 32,  8, 101, 9_999_999);
+decimal!(Decimal64, u64, 64, 10, 398, 9_999_999_999_999_999);
+
+
+impl From<Decimal32> for Decimal64 {
+    fn from(value: Decimal32) -> Decimal64 {
+        match value.unpack() {
+            Unpacked::NaN                   => Decimal64::NAN,
+            Unpacked::Infinity { negative } => if negative { Decimal64::NEG_INFINITY } else { Decimal64::INFINITY },
+            Unpacked::Finite { negative, coefficient, exponent } => {
+                // note: decimal64 has a superset of the range and precision.
+                Decimal64::from_parts(negative, coefficient as u128, exponent).unwrap()
+            },
+        }
+    }
+}
+
+
+#[cfg(feature = "rust_decimal")]
+mod rust_decimal_impls {
+    use rust_decimal::Decimal;
+    use super::*;
+
+    macro_rules! conversions {
+        ($name: ident) => {
+            impl TryFrom<Decimal> for $name {
+                type Error = DecimalError;
+
+                fn try_from(value: Decimal) -> Result<$name, DecimalError> {
+                    let exponent = -(value.scale() as i32);
+                    $name::from_parts(value.is_sign_negative(), value.mantissa().unsigned_abs(), exponent)
+                        .ok_or(DecimalError::Inexact)
+                }
+            }
+
+            impl TryFrom<$name> for Decimal {
+                type Error = DecimalError;
+
+                fn try_from(value: $name) -> Result<Decimal, DecimalError> {
+                    let (negative, coefficient, exponent) = value.to_parts().ok_or(DecimalError::NotFinite)?;
+
+                    // note: `Decimal` has a 96 bit mantissa, no positive
+                    // exponents, and at most 28 fractional digits.
+                    let (coefficient, exponent) = fit(coefficient as u128, exponent as i64, (1 << 96) - 1, -28, 0)
+                        .ok_or(DecimalError::Inexact)?;
+
+                    let coefficient = coefficient as i128;
+                    let scale       = -exponent as u32;
+
+                    let mantissa = if negative { -coefficient } else { coefficient };
+                    let mut result = Decimal::try_from_i128_with_scale(mantissa, scale)
+                        .map_err(|_| DecimalError::Inexact)?;
+                    result.set_sign_negative(negative);
+                    Ok(result)
+                }
+            }
+        };
+    }
+
+    conversions!(Decimal32);
+    conversions!(Decimal64);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d32(string: &str) -> Decimal32 {
+        string.parse().unwrap()
+    }
+
+    fn d64(string: &str) -> Decimal64 {
+        string.parse().unwrap()
+    }
+
+    #[test]
+    fn decimal32_bits() {
+        assert_eq!(d32("1").to_bits(),           0x3280_0001);
+        assert_eq!(d32("-0").to_bits(),          0xB280_0000);
+        assert_eq!(d32("9999999").to_bits(),     0x6CB8_967F);
+        assert_eq!(d32("1E+90").to_bits(),       0x5F80_0001);
+        assert_eq!(d32("9999999E+90").to_bits(), 0x77F8_967F);
+        assert_eq!(d32("1E-101").to_bits(),      0x0000_0001);
+        assert_eq!(d32("NaN").to_bits(),         0x7C00_0000);
+        assert_eq!(d32("Infinity").to_bits(),    0x7800_0000);
+        assert_eq!(d32("-Infinity").to_bits(),   0xF800_0000);
+
+        assert_eq!(Decimal32::from_bits(0x6CB8_967F).to_parts(), Some((false, 9_999_999, 0)));
+        assert_eq!(Decimal32::from_bits(0x77F8_967F).to_parts(), Some((false, 9_999_999, Decimal32::MAX_EXPONENT)));
+        assert_eq!(Decimal32::from_bits(0x0000_0001).to_parts(), Some((false, 1, Decimal32::MIN_EXPONENT)));
+        assert!(Decimal32::from_bits(0x7C00_0000).is_nan());
+    }
+
+    #[test]
+    fn decimal64_bits() {
+        assert_eq!(d64("1").to_bits(),                     0x31C0_0000_0000_0001);
+        assert_eq!(d64("-0").to_bits(),                    0xB1C0_0000_0000_0000);
+        assert_eq!(d64("9999999999999999").to_bits(),      0x6C73_86F2_6FC0_FFFF);
+        assert_eq!(d64("1E+369").to_bits(),                0x5FE0_0000_0000_0001);
+        assert_eq!(d64("9999999999999999E+369").to_bits(), 0x77FB_86F2_6FC0_FFFF);
+        assert_eq!(d64("1E-398").to_bits(),                0x0000_0000_0000_0001);
+        assert_eq!(d64("NaN").to_bits(),                   0x7C00_0000_0000_0000);
+        assert_eq!(d64("Infinity").to_bits(),              0x7800_0000_0000_0000);
+        assert_eq!(d64("-Infinity").to_bits(),             0xF800_0000_0000_0000);
+
+        assert_eq!(Decimal64::from_bits(0x6C73_86F2_6FC0_FFFF).to_parts(), Some((false, 9_999_999_999_999_999, 0)));
+        assert!(Decimal64::from_bits(0xF800_0000_0000_0000).is_infinite());
+        assert!(Decimal64::from_bits(0xB1C0_0000_0000_0000).is_sign_negative());
+    }
+
+    #[test]
+    fn string_round_trip() {
+        for string in ["1.10", "1E+3", "0.00", "-0", "-1.5", "0.000001", "1E-7", "9999999", "NaN", "Infinity", "-Infinity"] {
+            assert_eq!(d32(string).to_string(), string);
+            assert_eq!(d64(string).to_string(), string);
+        }
+        assert_eq!(d64("1.10").to_parts(), Some((false, 110, -2)));
+        assert_eq!(d64("1E+3").to_parts(), Some((false, 1, 3)));
+        assert_eq!(d64("0.00").to_parts(), Some((false, 0, -2)));
+        assert_eq!(d64("1000").to_string(), "1000");
+    }
+
+    #[test]
+    fn inexact() {
+        assert_eq!("12345678".parse::<Decimal32>(), Err(DecimalError::Inexact));
+        assert_eq!("1E-102".parse::<Decimal32>(),   Err(DecimalError::Inexact));
+        assert_eq!("1E+97".parse::<Decimal32>(),    Err(DecimalError::Inexact));
+        assert_eq!("12345678901234567".parse::<Decimal64>(), Err(DecimalError::Inexact));
+        assert_eq!("1.x".parse::<Decimal64>(),      Err(DecimalError::Invalid));
+
+        // note: trailing zeros and zero coefficients are scaled into range.
+        assert_eq!("123456780".parse::<Decimal32>(), Err(DecimalError::Inexact));
+        assert_eq!(d32("12345670").to_parts(), Some((false, 1_234_567, 1)));
+        assert_eq!(d32("1E+96").to_parts(),  Some((false, 1_000_000, Decimal32::MAX_EXPONENT)));
+        assert_eq!(d32("0E-200").to_parts(), Some((false, 0, Decimal32::MIN_EXPONENT)));
+    }
+
+    #[test]
+    fn equality_by_value() {
+        assert_eq!(d64("1.0"), d64("1.00"));
+        assert_ne!(d64("1.0").to_bits(), d64("1.00").to_bits());
+        assert_eq!(d64("0"), d64("-0"));
+        assert_eq!(d64("1E+3"), d64("1000"));
+        assert!(d64("1.01") > d64("1.0"));
+        assert!(d64("-2") < d64("-1.5"));
+        assert_ne!(d64("NaN"), d64("NaN"));
+        assert_eq!(Decimal64::from(d32("1.10")), d64("1.1"));
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn rust_decimal_conversions() {
+        use rust_decimal::Decimal;
+
+        let smallest = Decimal::new(1, 28);
+        let value = Decimal64::try_from(smallest).unwrap();
+        assert_eq!(value.to_parts(), Some((false, 1, -28)));
+        assert_eq!(Decimal::try_from(value).unwrap().scale(), 28);
+
+        let value = d64("-9999999999999999E-28");
+        let result = Decimal::try_from(value).unwrap();
+        assert_eq!(result, Decimal::new(-9_999_999_999_999_999, 28));
+        assert_eq!(Decimal64::try_from(result).unwrap().to_bits(), value.to_bits());
+
+        assert_eq!(Decimal::try_from(d64("1E-29")),    Err(DecimalError::Inexact));
+        assert_eq!(Decimal::try_from(d64("1E+3")),     Ok(Decimal::new(1000, 0)));
+        assert_eq!(Decimal::try_from(d64("Infinity")), Err(DecimalError::NotFinite));
+        assert_eq!(Decimal64::try_from(Decimal::new(1_234_567_890_123_456_789, 28)), Err(DecimalError::Inexact));
+        assert_eq!(Decimal32::try_from(Decimal::new(1_000_000_000, 28)).unwrap().to_parts(), Some((false, 1_000_000, -25)));
+    }
+}
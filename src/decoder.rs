@@ -1,31 +1,419 @@
+use std::collections::HashMap;
 use slice_reader::{Reader, byte_order::aliases::LE};
-use crate::{wire_type::*, utils::*};
+use crate::{wire_type::*, utils::*, decimal::{self, Decimal32, Decimal64}, dictionary::{Dictionary, Dictionaries}};
 
 
-pub fn decode_size_prefixed<'val>(reader: &mut Reader<'val, u8>) -> Option<&'val [u8]> {
-    let size = decode_size_as_usize::<LE>(reader)?;
-    reader.next_n(size)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Preamble,
+    Header,
+    Kind,
+    Tags,
+    Payload,
+    Trailing,
 }
 
-pub fn decode_length_prefixed(buffer: &[u8]) -> Option<(usize, Reader<u8>)> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    UnexpectedEnd,
+    InvalidWireType,
+    InvalidSymbolTable,
+    UnknownSymbol,
+    UnknownDictionary { id: u32, version: u32 },
+    DuplicateTag,
+    UnsortedTags,
+    DepthLimit,
+    ListTooLong,
+    DocumentTooLarge,
+    SizeTooLarge,
+    CountMismatch,
+    TrailingBytes,
+    InvalidUtf8,
+    // a well formed value a `codec::Decode` impl rejected.
+    InvalidValue (Invalid),
+}
+
+// why a `codec::Decode` impl rejected a value. the error's `offset`,
+// `header` and `path` locate the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Invalid {
+    // the value's wire type, `wire_type`, isn't `expected`.
+    UnexpectedType { expected: &'static str },
+    // the value's kind is missing, or not one the type knows.
+    UnexpectedKind,
+    // `expected` and `available` are the expected and found list lengths.
+    UnexpectedLength,
+    // the last segment of `path` is the missing tag.
+    MissingTag,
+    // a tag symbol or `Symbol` payload the type can't represent, like a
+    // map key that isn't utf-8. for tags, the last segment of `path` is
+    // the symbol.
+    InvalidSymbol,
+    OutOfRange,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Index (usize),
+    Tag   (Vec<u8>),
+}
+
+// the location of a value in the document tree, from the root down.
+// displayed as `$.users[17].tags["e-mail"]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Path(pub Vec<PathSegment>);
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "$")?;
+        for segment in &self.0 {
+            match segment {
+                PathSegment::Index (index) => write!(f, "[{}]", index)?,
+
+                PathSegment::Tag (symbol) => {
+                    let symbol = std::string::String::from_utf8_lossy(symbol);
+                    let is_ident =
+                        symbol.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                        && symbol.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    if is_ident {
+                        write!(f, ".{}", symbol)?;
+                    }
+                    else {
+                        write!(f, "[{:?}]", symbol)?;
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+
+// note: `offset` is relative to the root of the document, the buffer it was
+// decoded from, see `Document`. `TagDecoder`, `ListDecoder` and the
+// document functions rebase their errors, the functions that take a reader,
+// like `decode_value`, report offsets relative to it.
+// `validate` also reports the `path` of the failing value.
+// for the `ValidationOptions` limits, `expected` is the limit and
+// `available` the value found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError {
+    pub offset:    usize,
+    pub stage:     Stage,
+    pub reason:    Reason,
+    pub header:    Option<u8>,
+    pub wire_type: Option<WireType>,
+    pub expected:  usize,
+    pub available: usize,
+    pub path:      Path,
+}
+
+impl DecodeError {
+    pub fn new(stage: Stage, reason: Reason, reader: &Reader<u8>) -> DecodeError {
+        DecodeError {
+            offset:    reader.cursor,
+            stage,
+            reason,
+            header:    None,
+            wire_type: None,
+            expected:  0,
+            available: reader.remaining(),
+            path:      Path::default(),
+        }
+    }
+
+    pub fn unexpected_end(stage: Stage, reader: &Reader<u8>, expected: usize) -> DecodeError {
+        DecodeError { expected, ..DecodeError::new(stage, Reason::UnexpectedEnd, reader) }
+    }
+
+    // `offset` is relative to `string`.
+    pub fn invalid_utf8(header: Header, string: &[u8], error: std::str::Utf8Error) -> DecodeError {
+        DecodeError {
+            offset:    error.valid_up_to(),
+            stage:     Stage::Payload,
+            reason:    Reason::InvalidUtf8,
+            header:    None,
+            wire_type: None,
+            expected:  0,
+            available: string.len() - error.valid_up_to(),
+            path:      Path::default(),
+        }.with_header(header)
+    }
+
+    // `offset` is relative to the tags block or list payload that is nested
+    // too deep.
+    pub fn depth_limit(stage: Stage, max_depth: usize) -> DecodeError {
+        DecodeError {
+            offset:    0,
+            stage,
+            reason:    Reason::DepthLimit,
+            header:    None,
+            wire_type: None,
+            expected:  max_depth,
+            available: max_depth.saturating_add(1),
+            path:      Path::default(),
+        }
+    }
+
+    // `value` is well formed, but not valid for the type decoding it.
+    pub fn invalid_value(stage: Stage, invalid: Invalid, value: &Value) -> DecodeError {
+        DecodeError {
+            offset:    value.offset,
+            stage,
+            reason:    Reason::InvalidValue(invalid),
+            header:    None,
+            wire_type: None,
+            expected:  0,
+            available: 0,
+            path:      Path::default(),
+        }.with_header(value.header)
+    }
+
+    pub fn unexpected_type(expected: &'static str, value: &Value) -> DecodeError {
+        DecodeError::invalid_value(Stage::Payload, Invalid::UnexpectedType { expected }, value)
+    }
+
+    pub fn unexpected_kind(value: &Value) -> DecodeError {
+        DecodeError::invalid_value(Stage::Kind, Invalid::UnexpectedKind, value)
+    }
+
+    pub fn unexpected_length(value: &Value, expected: usize, found: usize) -> DecodeError {
+        DecodeError {
+            expected,
+            available: found,
+            ..DecodeError::invalid_value(Stage::Payload, Invalid::UnexpectedLength, value)
+        }
+    }
+
+    pub fn missing_tag(value: &Value, symbol: &[u8]) -> DecodeError {
+        DecodeError::invalid_value(Stage::Tags, Invalid::MissingTag, value).in_tag(symbol)
+    }
+
+    // a `Symbol` payload.
+    pub fn invalid_symbol(value: &Value) -> DecodeError {
+        DecodeError::invalid_value(Stage::Payload, Invalid::InvalidSymbol, value)
+    }
+
+    // a tag symbol of `value`.
+    pub fn invalid_tag(value: &Value, symbol: &[u8]) -> DecodeError {
+        DecodeError::invalid_value(Stage::Tags, Invalid::InvalidSymbol, value).in_tag(symbol)
+    }
+
+    pub fn out_of_range(value: &Value) -> DecodeError {
+        DecodeError::invalid_value(Stage::Payload, Invalid::OutOfRange, value)
+    }
+
+    pub fn at_stage(self, stage: Stage) -> DecodeError {
+        DecodeError { stage, ..self }
+    }
+
+    pub fn with_header(self, header: Header) -> DecodeError {
+        DecodeError {
+            header:    Some(header.to_u8()),
+            wire_type: Some(header.wire_type),
+            ..self
+        }
+    }
+
+    pub fn rebase(self, base: usize) -> DecodeError {
+        DecodeError { offset: base + self.offset, ..self }
+    }
+
+    // these prepend, as errors are annotated while unwinding to the root.
+    pub fn in_index(mut self, index: usize) -> DecodeError {
+        self.path.0.insert(0, PathSegment::Index(index));
+        self
+    }
+
+    pub fn in_tag(mut self, symbol: &[u8]) -> DecodeError {
+        self.path.0.insert(0, PathSegment::Tag(symbol.to_vec()));
+        self
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?} at offset {} while decoding {:?}", self.reason, self.offset, self.stage)?;
+
+        if let Some(wire_type) = self.wire_type {
+            write!(f, " of {:?}", wire_type)?;
+        }
+        else if let Some(header) = self.header {
+            write!(f, " (header byte {:#04x})", header)?;
+        }
+
+        match self.reason {
+            Reason::UnexpectedEnd | Reason::CountMismatch | Reason::InvalidValue(Invalid::UnexpectedLength) => {
+                write!(f, ": expected {}, available {}", self.expected, self.available)?;
+            },
+            Reason::DepthLimit | Reason::ListTooLong | Reason::DocumentTooLarge => {
+                write!(f, ": limit {}, found {}", self.expected, self.available)?;
+            },
+            _ => (),
+        }
+
+        if !self.path.0.is_empty() {
+            write!(f, " at {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+
+fn decode_size_as_usize_or_err(reader: &mut Reader<u8>) -> Result<usize, DecodeError> {
+    let size = decode_size::<LE>(reader).ok_or_else(|| {
+        let expected = reader.peek().map(|first| 1 << (first & 0b11)).unwrap_or(1);
+        DecodeError::unexpected_end(Stage::Payload, reader, expected)
+    })?;
+    u64_to_usize(size).ok_or_else(|| DecodeError::new(Stage::Payload, Reason::SizeTooLarge, reader))
+}
+
+fn next_n_or_err<'val>(reader: &mut Reader<'val, u8>, n: usize) -> Result<&'val [u8], DecodeError> {
+    reader.next_n(n).ok_or_else(|| DecodeError::unexpected_end(Stage::Payload, reader, n))
+}
+
+
+pub fn decode_size_prefixed<'val>(reader: &mut Reader<'val, u8>) -> Result<&'val [u8], DecodeError> {
+    let size = decode_size_as_usize_or_err(reader)?;
+    next_n_or_err(reader, size)
+}
+
+pub fn decode_length_prefixed(buffer: &[u8]) -> Result<(usize, Reader<'_, u8>), DecodeError> {
     let mut reader = Reader::new(buffer);
     let length =
-        if reader.has_some() { decode_size_as_usize::<LE>(&mut reader)? }
+        if reader.has_some() { decode_size_as_usize_or_err(&mut reader)? }
         else                 { 0 };
-    Some((length, reader))
+    Ok((length, reader))
 }
 
 
-pub fn decode_symbol<'val>(reader: &mut Reader<'val, u8>) -> Option<&'val [u8]> {
-    let size = decode_size::<LE>(reader)?;
+// symbols with an even size are references into the document's dictionary
+// and symbol table, see `DICTIONARY_MARKER` and `SYMBOL_TABLE_MARKER`.
+pub fn decode_symbol<'val>(reader: &mut Reader<'val, u8>, symbols: Symbols<'val>) -> Result<&'val [u8], DecodeError> {
+    let size = decode_size_as_usize_or_err(reader)?;
     let (size, is_bytes) = (size >> 1, size & 1 != 0);
     if is_bytes {
-        reader.next_n(u64_to_usize(size)?)
+        next_n_or_err(reader, size)
     }
     else {
-        // reserved.
-        None
+        symbols.get(size).ok_or_else(|| DecodeError::new(Stage::Payload, Reason::UnknownSymbol, reader))
+    }
+}
+
+
+// a view of a document's dictionary and symbol table.
+#[derive(Clone, Copy, Default)]
+pub struct Symbols<'val> {
+    dictionary: Option<&'val Dictionary>,
+    ends:       &'val [u8],
+    bytes:      &'val [u8],
+}
+
+impl<'val> Symbols<'val> {
+    pub fn len(&self) -> usize {
+        self.dictionary_len() + self.ends.len() / 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn dictionary(&self) -> Option<&'val Dictionary> {
+        self.dictionary
+    }
+
+    pub fn get(&self, index: usize) -> Option<&'val [u8]> {
+        if index < self.dictionary_len() {
+            return self.dictionary.and_then(|dictionary| dictionary.get(index));
+        }
+
+        let index = index - self.dictionary_len();
+        if index >= self.ends.len() / 4 {
+            return None;
+        }
+        let begin = if index > 0 { self.end(index - 1) } else { 0 };
+        self.bytes.get(begin..self.end(index))
+    }
+
+    fn dictionary_len(&self) -> usize {
+        self.dictionary.map_or(0, |dictionary| dictionary.len())
+    }
+
+    fn end(&self, index: usize) -> usize {
+        let end = &self.ends[4*index .. 4*index + 4];
+        u32::from_le_bytes([end[0], end[1], end[2], end[3]]) as usize
+    }
+}
+
+// what the values of a document share. error offsets are relative to
+// `root`, the buffer the document was decoded from. the tags and lists of
+// its values must be slices of `root`.
+#[derive(Clone, Copy)]
+pub struct Document<'val> {
+    pub root:      &'val [u8],
+    pub symbols:   Symbols<'val>,
+    pub max_depth: usize,
+}
+
+impl<'val> Document<'val> {
+    // a document without a preamble.
+    pub fn new(root: &'val [u8]) -> Document<'val> {
+        Document { root, symbols: Symbols::default(), max_depth: DecodeOptions::DEFAULT_MAX_DEPTH }
+    }
+}
+
+// reads the dictionary reference and the symbol table, if the document has
+// them. the dictionary must be one of `dictionaries`.
+pub fn decode_preamble<'val>(reader: &mut Reader<'val, u8>, dictionaries: &'val Dictionaries) -> Result<Symbols<'val>, DecodeError> {
+    let mut dictionary = None;
+    if reader.peek() == Some(&DICTIONARY_MARKER) {
+        reader.next_u8_le();
+
+        let decode_u32 = |reader: &mut Reader<u8>| {
+            let value = decode_size_as_usize_or_err(reader).map_err(|e| e.at_stage(Stage::Preamble))?;
+            u32::try_from(value).map_err(|_| DecodeError::new(Stage::Preamble, Reason::SizeTooLarge, reader))
+        };
+        let offset  = reader.cursor;
+        let id      = decode_u32(reader)?;
+        let version = decode_u32(reader)?;
+
+        dictionary = Some(dictionaries.get(id, version).ok_or_else(|| DecodeError {
+            offset,
+            ..DecodeError::new(Stage::Preamble, Reason::UnknownDictionary { id, version }, reader)
+        })?);
+    }
+
+    Ok(Symbols { dictionary, ..decode_symbol_table(reader)? })
+}
+
+fn decode_symbol_table<'val>(reader: &mut Reader<'val, u8>) -> Result<Symbols<'val>, DecodeError> {
+    if reader.peek() != Some(&SYMBOL_TABLE_MARKER) {
+        return Ok(Symbols::default());
+    }
+    reader.next_u8_le();
+
+    let invalid = |reader: &Reader<u8>| DecodeError::new(Stage::Preamble, Reason::InvalidSymbolTable, reader);
+
+    let table = decode_size_prefixed(reader).map_err(|e| e.at_stage(Stage::Preamble))?;
+    let (length, mut table) = decode_length_prefixed(table).map_err(|e| e.at_stage(Stage::Preamble))?;
+    let ends = length.checked_mul(4).and_then(|size| table.next_n(size)).ok_or_else(|| invalid(reader))?;
+    let symbols = Symbols { dictionary: None, ends, bytes: table.next_n(table.remaining()).unwrap() };
+
+    let mut previous = 0;
+    for index in 0..length {
+        let end = symbols.end(index);
+        if end < previous {
+            return Err(invalid(reader));
+        }
+        previous = end;
+    }
+    if previous != symbols.bytes.len() {
+        return Err(invalid(reader));
     }
+
+    Ok(symbols)
 }
 
 
@@ -37,40 +425,59 @@ pub struct Header {
     pub has_tags:  bool,
 }
 
-pub fn decode_header(reader: &mut Reader<u8>) -> Option<Header> {
-    let header = reader.next_u8_le()?;
-    Some(Header {
-        wire_type: WireType::from_u8(header & WIRE_TYPE_MASK)?,
+impl Header {
+    pub fn to_u8(&self) -> u8 {
+        let mut header = self.wire_type as u8;
+        if self.has_kind { header |= WIRE_FLAG_KIND }
+        if self.has_tags { header |= WIRE_FLAG_TAGS }
+        header
+    }
+}
+
+pub fn decode_header(reader: &mut Reader<u8>) -> Result<Header, DecodeError> {
+    let header = reader.next_u8_le()
+        .ok_or_else(|| DecodeError::unexpected_end(Stage::Header, reader, 1))?;
+
+    let wire_type = WireType::from_u8(header & WIRE_TYPE_MASK).ok_or_else(|| DecodeError {
+        offset: reader.cursor - 1,
+        header: Some(header),
+        ..DecodeError::new(Stage::Header, Reason::InvalidWireType, reader)
+    })?;
+
+    Ok(Header {
+        wire_type,
         has_kind: header & WIRE_FLAG_KIND != 0,
         has_tags: header & WIRE_FLAG_TAGS != 0,
     })
 }
 
 
-pub fn decode_kind<'val>(has_kind: bool, reader: &mut Reader<'val, u8>) -> Option<&'val [u8]> {
+pub fn decode_kind<'val>(has_kind: bool, reader: &mut Reader<'val, u8>, symbols: Symbols<'val>) -> Result<&'val [u8], DecodeError> {
     if has_kind {
-        decode_symbol(reader)
+        decode_symbol(reader, symbols).map_err(|e| e.at_stage(Stage::Kind))
     }
     else {
-        Some(&reader.buffer[0..0])
+        Ok(&reader.buffer[0..0])
     }
 }
 
 
-pub fn decode_tags<'val>(has_tags: bool, reader: &mut Reader<'val, u8>) -> Option<&'val [u8]> {
+pub fn decode_tags<'val>(has_tags: bool, reader: &mut Reader<'val, u8>) -> Result<&'val [u8], DecodeError> {
     if has_tags {
-        decode_size_prefixed(reader)
+        decode_size_prefixed(reader).map_err(|e| e.at_stage(Stage::Tags))
     }
     else {
-        Some(&reader.buffer[0..0])
+        Ok(&reader.buffer[0..0])
     }
 }
 
-pub fn decode_tag<'val>(reader: &mut Reader<'val, u8>) -> Option<(&'val [u8], Value<'val>)> {
-    Some((decode_symbol(reader)?, decode_value(reader)?))
+pub fn decode_tag<'val>(reader: &mut Reader<'val, u8>, document: Document<'val>, depth: usize) -> Result<(&'val [u8], Value<'val>), DecodeError> {
+    let symbol = decode_symbol(reader, document.symbols).map_err(|e| e.at_stage(Stage::Tags))?;
+    Ok((symbol, decode_value(reader, document, depth)?))
 }
 
 
+#[derive(Clone, Copy)]
 pub enum Payload<'val> {
     Null,
     Bool      (bool),
@@ -86,87 +493,279 @@ pub enum Payload<'val> {
     Int64     (i64),
     Float32   (f32),
     Float64   (f64),
-    Decimal32 ([u8; 4]),
-    Decimal64 ([u8; 8]),
+    Decimal32 (Decimal32),
+    Decimal64 (Decimal64),
     Bytes     (&'val [u8]),
     String    (&'val [u8]),
     Symbol    (&'val [u8]),
     List      (&'val [u8]),
 }
 
-pub fn decode_payload<'val>(ty: WireType, reader: &mut Reader<'val, u8>) -> Option<Payload<'val>> {
+fn fixed_payload_size(ty: WireType) -> usize {
     use WireType::*;
-    Some(match ty {
+    match ty {
+        Nat8  | Int8                          => 1,
+        Nat16 | Int16                         => 2,
+        Nat32 | Int32 | Float32 | Decimal32   => 4,
+        Nat64 | Int64 | Float64 | Decimal64   => 8,
+        _ => 0,
+    }
+}
+
+pub fn decode_payload<'val>(ty: WireType, reader: &mut Reader<'val, u8>, symbols: Symbols<'val>) -> Result<Payload<'val>, DecodeError> {
+    use WireType::*;
+
+    let size = fixed_payload_size(ty);
+    if reader.remaining() < size {
+        return Err(DecodeError::unexpected_end(Stage::Payload, reader, size));
+    }
+
+    // note: the fixed size reads below can't fail.
+    Ok(match ty {
         Null      => { Payload::Null },
         BoolFalse => { Payload::Bool(false) },
         BoolTrue  => { Payload::Bool(true) },
-        Nat8      => { Payload::Nat8(reader.next_u8_le()?) },
-        Nat16     => { Payload::Nat16(reader.next_u16_le()?) },
-        Nat32     => { Payload::Nat32(reader.next_u32_le()?) },
-        Nat64     => { Payload::Nat64(reader.next_u64_le()?) },
-        Int8      => { Payload::Int8(reader.next_i8_le()?) },
-        Int16     => { Payload::Int16(reader.next_i16_le()?) },
-        Int32     => { Payload::Int32(reader.next_i32_le()?) },
-        Int64     => { Payload::Int64(reader.next_i64_le()?) },
-        Float32   => { Payload::Float32(reader.next_f32_le()?) },
-        Float64   => { Payload::Float64(reader.next_f64_le()?) },
-        Decimal32 => { Payload::Decimal32(reader.next_bytes_endian::<4, LE>()?) },
-        Decimal64 => { Payload::Decimal64(reader.next_bytes_endian::<8, LE>()?) },
+        Nat8      => { Payload::Nat8(reader.next_u8_le().unwrap()) },
+        Nat16     => { Payload::Nat16(reader.next_u16_le().unwrap()) },
+        Nat32     => { Payload::Nat32(reader.next_u32_le().unwrap()) },
+        Nat64     => { Payload::Nat64(reader.next_u64_le().unwrap()) },
+        Int8      => { Payload::Int8(reader.next_i8_le().unwrap()) },
+        Int16     => { Payload::Int16(reader.next_i16_le().unwrap()) },
+        Int32     => { Payload::Int32(reader.next_i32_le().unwrap()) },
+        Int64     => { Payload::Int64(reader.next_i64_le().unwrap()) },
+        Float32   => { Payload::Float32(reader.next_f32_le().unwrap()) },
+        Float64   => { Payload::Float64(reader.next_f64_le().unwrap()) },
+        Decimal32 => { Payload::Decimal32(decimal::Decimal32::from_bits(reader.next_u32_le().unwrap())) },
+        Decimal64 => { Payload::Decimal64(decimal::Decimal64::from_bits(reader.next_u64_le().unwrap())) },
         Nat       => { Payload::Nat(decode_size_prefixed(reader)?) },
         Int       => { Payload::Int(decode_size_prefixed(reader)?) },
         Bytes     => { Payload::Bytes(decode_size_prefixed(reader)?) },
         String    => { Payload::String(decode_size_prefixed(reader)?) },
-        Symbol    => { Payload::Symbol(decode_symbol(reader)?) },
+        Symbol    => { Payload::Symbol(decode_symbol(reader, symbols)?) },
         List      => { Payload::List(decode_size_prefixed(reader)?) },
     })
 }
 
 
+#[derive(Clone, Copy)]
 pub struct Value<'val> {
-    pub header:  Header,
-    pub kind:    &'val [u8],
-    pub tags:    &'val [u8],
-    pub payload: Payload<'val>,
+    // the offset of the header in the document's root.
+    pub offset:   usize,
+    pub header:   Header,
+    pub kind:     &'val [u8],
+    pub tags:     &'val [u8],
+    pub payload:  Payload<'val>,
+    // the number of tags blocks and lists the value is nested in.
+    pub depth:    usize,
+    pub document: Document<'val>,
 }
 
 impl<'val> Value<'val> {
-    pub fn tags(&self) -> Option<TagDecoder> {
-        TagDecoder::new(self.tags)
+    pub fn tags(&self) -> Result<TagDecoder<'val>, DecodeError> {
+        TagDecoder::new(self.tags, self.document, self.depth)
+    }
+
+    // the first tag with `symbol`, `None` if there is none.
+    // note: the tags after the match are not decoded, so their errors go
+    // unreported.
+    pub fn tag(&self, symbol: &[u8]) -> Result<Option<Value<'val>>, DecodeError> {
+        let mut tags = self.tags()?;
+        match tags.find(|(tag, _)| *tag == symbol) {
+            Some((_, value)) => Ok(Some(value)),
+            None => tags.check_error().map(|()| None),
+        }
+    }
+
+    // like `tags_map_with(Duplicates::First)`, consistent with `tag`.
+    pub fn tags_map(&self) -> Result<TagMap<'val>, DecodeError> {
+        self.tags_map_with(Duplicates::First)
     }
+
+    pub fn tags_map_with(&self, duplicates: Duplicates) -> Result<TagMap<'val>, DecodeError> {
+        TagMap::new(self.tags()?, duplicates)
+    }
+
+    // the integer accessors accept any integer wire type, if the value fits.
+    pub fn as_u128(&self) -> Result<u128, NumberError> {
+        let result = match self.payload {
+            Payload::Nat8  (value) => Some(value as u128),
+            Payload::Nat16 (value) => Some(value as u128),
+            Payload::Nat32 (value) => Some(value as u128),
+            Payload::Nat64 (value) => Some(value as u128),
+            Payload::Int8  (value) => u128::try_from(value).ok(),
+            Payload::Int16 (value) => u128::try_from(value).ok(),
+            Payload::Int32 (value) => u128::try_from(value).ok(),
+            Payload::Int64 (value) => u128::try_from(value).ok(),
+            Payload::Nat   (bytes) => nat_to_u128(bytes),
+            Payload::Int   (bytes) => int_to_i128(bytes).and_then(|value| u128::try_from(value).ok()),
+            _ => return Err(NumberError::UnexpectedType(self.header.wire_type)),
+        };
+        result.ok_or(NumberError::OutOfRange(self.header.wire_type))
+    }
+
+    pub fn as_i128(&self) -> Result<i128, NumberError> {
+        let result = match self.payload {
+            Payload::Nat8  (value) => Some(value as i128),
+            Payload::Nat16 (value) => Some(value as i128),
+            Payload::Nat32 (value) => Some(value as i128),
+            Payload::Nat64 (value) => Some(value as i128),
+            Payload::Int8  (value) => Some(value as i128),
+            Payload::Int16 (value) => Some(value as i128),
+            Payload::Int32 (value) => Some(value as i128),
+            Payload::Int64 (value) => Some(value as i128),
+            Payload::Nat   (bytes) => nat_to_u128(bytes).and_then(|value| i128::try_from(value).ok()),
+            Payload::Int   (bytes) => int_to_i128(bytes),
+            _ => return Err(NumberError::UnexpectedType(self.header.wire_type)),
+        };
+        result.ok_or(NumberError::OutOfRange(self.header.wire_type))
+    }
+
+    pub fn as_u64(&self) -> Result<u64, NumberError> {
+        u64::try_from(self.as_u128()?).map_err(|_| NumberError::OutOfRange(self.header.wire_type))
+    }
+
+    pub fn as_i64(&self) -> Result<i64, NumberError> {
+        i64::try_from(self.as_i128()?).map_err(|_| NumberError::OutOfRange(self.header.wire_type))
+    }
+
+    // accepts floats, and integers that convert exactly.
+    pub fn as_f64(&self) -> Result<f64, NumberError> {
+        match self.payload {
+            Payload::Float32 (value) => return Ok(value as f64),
+            Payload::Float64 (value) => return Ok(value),
+            _ => (),
+        }
+
+        // note: `as` saturates, so the upper bounds need an explicit check.
+        let result = match self.as_i128() {
+            Ok(value) => {
+                let result = value as f64;
+                (result != 2f64.powi(127) && result as i128 == value).then_some(result)
+            },
+            Err(NumberError::OutOfRange(_)) => {
+                let value = self.as_u128()?;
+                let result = value as f64;
+                (result != 2f64.powi(128) && result as u128 == value).then_some(result)
+            },
+            Err(error) => return Err(error),
+        };
+        result.ok_or(NumberError::OutOfRange(self.header.wire_type))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberError {
+    UnexpectedType (WireType),
+    OutOfRange     (WireType),
 }
 
-pub fn decode_value<'rdr>(reader: &mut Reader<'rdr, u8>) -> Option<Value<'rdr>> {
+impl std::fmt::Display for NumberError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NumberError::UnexpectedType (wire_type) => write!(f, "expected a number, found {:?}", wire_type),
+            NumberError::OutOfRange     (wire_type) => write!(f, "{:?} out of range", wire_type),
+        }
+    }
+}
+
+impl std::error::Error for NumberError {}
+
+// `reader` must read `document.root`, or a part of it.
+pub fn decode_value<'rdr>(reader: &mut Reader<'rdr, u8>, document: Document<'rdr>, depth: usize) -> Result<Value<'rdr>, DecodeError> {
+    let offset = offset_in(document.root, reader.buffer) + reader.cursor;
     let header = decode_header(reader)?;
-    Some(Value {
+    Ok(Value {
+        offset,
         header,
-        kind:    decode_kind(header.has_kind, reader)?,
-        tags:    decode_tags(header.has_tags, reader)?,
-        payload: decode_payload(header.wire_type, reader)?,
+        kind:    decode_kind(header.has_kind, reader, document.symbols).map_err(|e| e.with_header(header))?,
+        tags:    decode_tags(header.has_tags, reader).map_err(|e| e.with_header(header))?,
+        payload: decode_payload(header.wire_type, reader, document.symbols).map_err(|e| e.with_header(header))?,
+        depth,
+        document,
     })
 }
 
+// how documents are decoded. shared by the decoding entry points of all
+// modules, the defaults decode documents without a dictionary reference.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeOptions<'dict> {
+    dictionaries: &'dict Dictionaries,
+    max_depth:    usize,
+}
+
+impl DecodeOptions<'static> {
+    pub fn new() -> DecodeOptions<'static> {
+        DecodeOptions {
+            dictionaries: Dictionaries::empty(),
+            max_depth:    DecodeOptions::DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+impl<'dict> DecodeOptions<'dict> {
+    pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+    // the dictionaries a document may be encoded against.
+    pub fn dictionaries<'new>(self, dictionaries: &'new Dictionaries) -> DecodeOptions<'new> {
+        DecodeOptions { dictionaries, max_depth: self.max_depth }
+    }
+
+    // tags blocks and lists are only entered by values at depths below
+    // `max_depth`, deeper ones fail with `Reason::DepthLimit`. the root
+    // value is at depth 0.
+    // note: apart from `validate`, decoding recurses once per level, a
+    // large limit needs a large stack.
+    pub fn max_depth(self, max_depth: usize) -> DecodeOptions<'dict> {
+        DecodeOptions { max_depth, ..self }
+    }
+}
+
+impl Default for DecodeOptions<'static> {
+    fn default() -> DecodeOptions<'static> {
+        DecodeOptions::new()
+    }
+}
+
+// the root value of a document, after its preamble.
+pub fn decode_document<'rdr>(reader: &mut Reader<'rdr, u8>) -> Result<Value<'rdr>, DecodeError> {
+    decode_document_with_options(reader, &DecodeOptions::new())
+}
+
+pub fn decode_document_with_options<'rdr>(reader: &mut Reader<'rdr, u8>, options: &DecodeOptions<'rdr>) -> Result<Value<'rdr>, DecodeError> {
+    let symbols = decode_preamble(reader, options.dictionaries)?;
+    decode_value(reader, Document { root: reader.buffer, symbols, max_depth: options.max_depth }, 0)
+}
+
 
 
 
+// note: iteration stops at the first error. `check_error` reports it.
+// `depth` is the depth of the value the tags belong to, `base` the offset of
+// the tags block in the document's root. `reader` reads the tags block.
 pub struct TagDecoder<'val> {
     pub remaining: usize,
+    pub base:      usize,
     pub reader:    Reader<'val, u8>,
+    pub document:  Document<'val>,
+    pub depth:     usize,
+    pub error:     Option<DecodeError>,
 }
 
 impl<'val> TagDecoder<'val> {
-    pub fn new(tags: &'val [u8]) -> Option<TagDecoder> {
-        let (remaining, reader) = decode_length_prefixed(tags)?;
-        if reader.remaining() < 2*remaining {
-            return None;
+    pub fn new(tags: &'val [u8], document: Document<'val>, depth: usize) -> Result<TagDecoder<'val>, DecodeError> {
+        let base = offset_in(document.root, tags);
+        let (remaining, reader) = decode_length_prefixed(tags).map_err(|e| e.at_stage(Stage::Tags).rebase(base))?;
+        if reader.remaining() < remaining.saturating_mul(2) {
+            return Err(DecodeError::unexpected_end(Stage::Tags, &reader, remaining.saturating_mul(2)).rebase(base));
+        }
+        if remaining > 0 && depth >= document.max_depth {
+            return Err(DecodeError::depth_limit(Stage::Tags, document.max_depth).rebase(base));
         }
-        Some(TagDecoder { remaining, reader })
+        Ok(TagDecoder { remaining, base, reader, document, depth, error: None })
     }
 
-    pub fn check_error(self) -> Result<(), ()> {
-        if self.remaining != 0 || self.reader.has_some() {
-            return Err(());
-        }
-        Ok(())
+    pub fn check_error(self) -> Result<(), DecodeError> {
+        check_error(self.remaining, self.base, &self.reader, self.error, Stage::Tags)
     }
 }
 
@@ -174,36 +773,159 @@ impl<'val> Iterator for TagDecoder<'val> {
     type Item = (&'val [u8], Value<'val>);
 
     fn next(&mut self) -> Option<(&'val [u8], Value<'val>)> {
-        if self.remaining > 0 {
-            let result = decode_tag(&mut self.reader)?;
-            // remaining != 0 => error.
-            self.remaining -= 1;
-            return Some(result)
+        if self.remaining > 0 && self.error.is_none() {
+            match decode_tag(&mut self.reader, self.document, self.depth + 1) {
+                Ok(result) => {
+                    self.remaining -= 1;
+                    return Some(result)
+                },
+                Err(error) => {
+                    // remaining != 0 => error.
+                    self.error = Some(error.rebase(self.base));
+                },
+            }
         }
         None
     }
 }
 
 
+// how `TagMap` treats a symbol that occurs more than once in a tags block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplicates {
+    First,
+    Last,
+    Reject,
+}
+
+// the tags of a value, by symbol, in their original order.
+// maps with more than `TagMap::LINEAR_MAX` tags are indexed by a hash map.
+#[derive(Clone)]
+pub struct TagMap<'val> {
+    tags:  Vec<(&'val [u8], Value<'val>)>,
+    index: HashMap<&'val [u8], usize>,
+}
+
+impl<'val> TagMap<'val> {
+    pub const LINEAR_MAX: usize = 8;
+
+    // `Duplicates::Reject` fails with `Reason::DuplicateTag` at the second
+    // occurrence.
+    pub fn new(mut tags: TagDecoder<'val>, duplicates: Duplicates) -> Result<TagMap<'val>, DecodeError> {
+        let indexed = tags.remaining > TagMap::LINEAR_MAX;
+        let mut map = TagMap {
+            tags:  Vec::with_capacity(tags.remaining),
+            index: HashMap::with_capacity(if indexed { tags.remaining } else { 0 }),
+        };
+
+        loop {
+            let offset = tags.base + tags.reader.cursor;
+            let (symbol, value) = match tags.next() {
+                Some(tag) => tag,
+                None      => break,
+            };
+
+            match map.position(symbol) {
+                None => {
+                    if indexed {
+                        map.index.insert(symbol, map.tags.len());
+                    }
+                    map.tags.push((symbol, value));
+                },
+
+                Some(position) => match duplicates {
+                    Duplicates::First  => (),
+                    Duplicates::Last   => map.tags[position].1 = value,
+                    Duplicates::Reject => {
+                        let error = DecodeError { offset, ..DecodeError::new(Stage::Tags, Reason::DuplicateTag, &tags.reader) };
+                        return Err(error.in_tag(symbol));
+                    },
+                },
+            }
+        }
+        tags.check_error()?;
+
+        Ok(map)
+    }
+
+    fn position(&self, symbol: &[u8]) -> Option<usize> {
+        if self.index.is_empty() {
+            self.tags.iter().position(|(tag, _)| *tag == symbol)
+        }
+        else {
+            self.index.get(symbol).copied()
+        }
+    }
+
+    pub fn get(&self, symbol: &[u8]) -> Option<Value<'val>> {
+        self.position(symbol).map(|position| self.tags[position].1)
+    }
+
+    pub fn contains(&self, symbol: &[u8]) -> bool {
+        self.position(symbol).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'val [u8], Value<'val>)> + '_ {
+        self.tags.iter().copied()
+    }
+}
+
+
+// `depth` is the depth of the value the list belongs to, `base` the offset
+// of the list payload in the document's root. `reader` reads the payload.
 pub struct ListDecoder<'val> {
+    pub length:    usize,
     pub remaining: usize,
+    pub base:      usize,
     pub reader:    Reader<'val, u8>,
+    pub document:  Document<'val>,
+    pub depth:     usize,
+    pub error:     Option<DecodeError>,
 }
 
 impl<'val> ListDecoder<'val> {
-    pub fn new(payload: &'val [u8]) -> Option<ListDecoder> {
-        let (remaining, reader) = decode_length_prefixed(payload)?;
-        if reader.remaining() < 1*remaining {
-            return None;
+    pub fn new(payload: &'val [u8], document: Document<'val>, depth: usize) -> Result<ListDecoder<'val>, DecodeError> {
+        let base = offset_in(document.root, payload);
+        let (remaining, reader) = decode_length_prefixed(payload).map_err(|e| e.rebase(base))?;
+        if reader.remaining() < remaining {
+            return Err(DecodeError::unexpected_end(Stage::Payload, &reader, remaining).rebase(base));
+        }
+        if remaining > 0 && depth >= document.max_depth {
+            return Err(DecodeError::depth_limit(Stage::Payload, document.max_depth).rebase(base));
         }
-        Some(ListDecoder { remaining, reader })
+        Ok(ListDecoder { length: remaining, remaining, base, reader, document, depth, error: None })
+    }
+
+    pub fn check_error(self) -> Result<(), DecodeError> {
+        check_error(self.remaining, self.base, &self.reader, self.error, Stage::Payload)
+    }
+
+    // the index of the element that failed to decode, if one did.
+    pub fn failed_index(&self) -> Option<usize> {
+        self.error.as_ref().map(|_| self.length - self.remaining)
+    }
+
+    // the error of the element that failed to decode, with its index in
+    // the path.
+    pub fn take_element_error(&mut self) -> Option<DecodeError> {
+        let index = self.failed_index()?;
+        self.error.take().map(|error| error.in_index(index))
     }
 
-    pub fn check_error(self) -> Result<(), ()> {
-        if self.remaining != 0 || self.reader.has_some() {
-            return Err(());
+    // like `check_error`, but with the index of the failed element.
+    pub fn check_element_error(mut self) -> Result<(), DecodeError> {
+        match self.take_element_error() {
+            Some(error) => Err(error),
+            None        => self.check_error(),
         }
-        Ok(())
     }
 }
 
@@ -211,13 +933,599 @@ impl<'val> Iterator for ListDecoder<'val> {
     type Item = Value<'val>;
 
     fn next(&mut self) -> Option<Value<'val>> {
-        if self.remaining > 0 {
-            let result = decode_value(&mut self.reader)?;
-            // remaining != 0 => error.
-            self.remaining -= 1;
-            return Some(result)
+        if self.remaining > 0 && self.error.is_none() {
+            match decode_value(&mut self.reader, self.document, self.depth + 1) {
+                Ok(result) => {
+                    self.remaining -= 1;
+                    return Some(result)
+                },
+                Err(error) => {
+                    // remaining != 0 => error.
+                    self.error = Some(error.rebase(self.base));
+                },
+            }
         }
         None
     }
 }
 
+
+fn check_error(remaining: usize, base: usize, reader: &Reader<u8>, error: Option<DecodeError>, stage: Stage) -> Result<(), DecodeError> {
+    if let Some(error) = error {
+        return Err(error);
+    }
+    if remaining != 0 {
+        return Err(DecodeError {
+            expected: remaining,
+            ..DecodeError::new(stage, Reason::CountMismatch, reader)
+        }.rebase(base));
+    }
+    if reader.has_some() {
+        return Err(DecodeError::new(Stage::Trailing, Reason::TrailingBytes, reader).rebase(base));
+    }
+    Ok(())
+}
+
+
+// walks a value and everything nested in it depth first, with an explicit
+// stack instead of recursion, so deeply nested input can't overflow the call
+// stack. values are yielded before their tags and elements, nesting deeper
+// than the document's `max_depth` fails with `Reason::DepthLimit`.
+// note: error offsets are relative to the document's root. iteration stops
+// at the first error.
+pub struct Walker<'val> {
+    stack:    Vec<Frame<'val>>,
+    first:    Option<Value<'val>>,
+    // the value yielded last. its tags and list are entered by the next call.
+    entering: Option<Value<'val>>,
+    failed:   bool,
+}
+
+enum Frame<'val> {
+    Tags    { tags: TagDecoder<'val>, symbol: &'val [u8] },
+    // the list of a value, entered after its tags.
+    Pending { header: Header, list: &'val [u8], document: Document<'val>, depth: usize },
+    List    { list: ListDecoder<'val> },
+}
+
+impl<'val> Walker<'val> {
+    pub fn new(value: Value<'val>) -> Walker<'val> {
+        Walker { stack: vec![], first: Some(value), entering: None, failed: false }
+    }
+
+    // the path of the value yielded last.
+    pub fn path(&self) -> Path {
+        let mut path = Path::default();
+        for frame in &self.stack {
+            match frame {
+                Frame::Tags    { symbol, .. } => path.0.push(PathSegment::Tag(symbol.to_vec())),
+                Frame::List    { list, .. }   => path.0.push(PathSegment::Index(list.length - list.remaining - 1)),
+                Frame::Pending { .. }         => (),
+            }
+        }
+        path
+    }
+
+    // prepends the path of the value yielded last, for errors found while
+    // checking it.
+    pub fn annotate(&self, mut error: DecodeError) -> DecodeError {
+        let mut path = self.path();
+        path.0.append(&mut error.path.0);
+        error.path = path;
+        error
+    }
+
+    fn visit(&mut self, value: Value<'val>) -> Option<Result<Value<'val>, DecodeError>> {
+        self.entering = Some(value);
+        Some(Ok(value))
+    }
+
+    fn fail(&mut self, error: DecodeError) -> Option<Result<Value<'val>, DecodeError>> {
+        self.failed = true;
+        Some(Err(self.annotate(error)))
+    }
+
+    fn enter(&mut self, value: Value<'val>) -> Result<(), DecodeError> {
+        if let Payload::List (list) = value.payload {
+            self.stack.push(Frame::Pending { header: value.header, list, document: value.document, depth: value.depth });
+        }
+
+        if value.header.has_tags {
+            let tags = value.tags().map_err(|e| e.with_header(value.header))?;
+            self.stack.push(Frame::Tags { tags, symbol: &[] });
+        }
+        Ok(())
+    }
+}
+
+impl<'val> Iterator for Walker<'val> {
+    type Item = Result<Value<'val>, DecodeError>;
+
+    fn next(&mut self) -> Option<Result<Value<'val>, DecodeError>> {
+        if self.failed {
+            return None;
+        }
+        if let Some(value) = self.first.take() {
+            return self.visit(value);
+        }
+        if let Some(value) = self.entering.take() {
+            if let Err(error) = self.enter(value) {
+                return self.fail(error);
+            }
+        }
+
+        // note: the frame on top is popped, and pushed back if it has more
+        // values, so the path is the owner's when it fails.
+        loop {
+            match self.stack.pop()? {
+                Frame::Tags { mut tags, .. } => {
+                    if let Some((symbol, value)) = tags.next() {
+                        self.stack.push(Frame::Tags { tags, symbol });
+                        return self.visit(value);
+                    }
+                    if let Err(error) = tags.check_error() {
+                        return self.fail(error);
+                    }
+                },
+
+                Frame::Pending { header, list, document, depth } => {
+                    let list = match ListDecoder::new(list, document, depth) {
+                        Ok(list)   => list,
+                        Err(error) => return self.fail(error.with_header(header)),
+                    };
+                    self.stack.push(Frame::List { list });
+                },
+
+                Frame::List { mut list } => {
+                    if let Some(value) = list.next() {
+                        self.stack.push(Frame::List { list });
+                        return self.visit(value);
+                    }
+                    if let Err(error) = list.check_element_error() {
+                        return self.fail(error);
+                    }
+                },
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::encoder::{Encoder, EncoderOptions};
+    use crate::value::{self, OwnedValue, OwnedPayload};
+
+    // a symbol table with "ab" and "c", then `root`.
+    fn document(ends: [u32; 2], root: &[u8]) -> Vec<u8> {
+        let mut result = vec![SYMBOL_TABLE_MARKER, 12 << 2, 2 << 2];
+        for end in ends {
+            result.extend(end.to_le_bytes());
+        }
+        result.extend(b"abc");
+        result.extend(root);
+        result
+    }
+
+    // a symbol payload that references `index`.
+    fn reference(index: u8) -> [u8; 2] {
+        [WireType::Symbol as u8, index << 3]
+    }
+
+    fn decode_symbol(buffer: &[u8], dictionaries: &Dictionaries) -> Result<Vec<u8>, DecodeError> {
+        let mut reader = Reader::new(buffer);
+        let value = decode_document_with_options(&mut reader, &DecodeOptions::new().dictionaries(dictionaries))?;
+        match value.payload {
+            Payload::Symbol (symbol) => Ok(symbol.to_vec()),
+            _ => panic!("not a symbol"),
+        }
+    }
+
+    fn symbol(symbol: &[u8]) -> OwnedValue {
+        OwnedValue::new(OwnedPayload::Symbol(symbol.to_vec()))
+    }
+
+    #[test]
+    fn interned_round_trip() {
+        let value = OwnedValue {
+            kind:    Some(b"alpha".to_vec()),
+            tags:    Some(vec![(b"beta".to_vec(), symbol(b"alpha"))]),
+            payload: OwnedPayload::List(vec![symbol(b"beta"), symbol(b"gamma"), symbol(b"beta"), symbol(b"")]),
+        };
+
+        for compress_sizes in [true, false] {
+            let mut encoder = Encoder::new(EncoderOptions::new().intern_symbols(true).compress_sizes(compress_sizes)).unwrap();
+            value.encode(&mut encoder);
+            let bytes = encoder.build().unwrap();
+
+            // note: first uses stay inline, later ones reference the table.
+            let count = |symbol: &[u8]| bytes.windows(symbol.len()).filter(|window| *window == symbol).count();
+            assert_eq!(bytes[0], SYMBOL_TABLE_MARKER);
+            assert_eq!(count(b"alpha"), 2);
+            assert_eq!(count(b"beta"),  2);
+            assert_eq!(count(b"gamma"), 1);
+            crate::validate(&bytes).unwrap();
+            assert_eq!(value::decode_owned(&bytes).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn interned_with_dictionary() {
+        let dictionary = Arc::new(Dictionary::new(7, 1, ["alpha"]));
+        let value = OwnedValue::new(OwnedPayload::List(vec![symbol(b"alpha"), symbol(b"beta"), symbol(b"beta"), symbol(b"alpha")]));
+
+        let mut encoder = Encoder::new(EncoderOptions::new().intern_symbols(true).dictionary(dictionary.clone())).unwrap();
+        value.encode(&mut encoder);
+        let bytes = encoder.build().unwrap();
+        assert_eq!(bytes[0], DICTIONARY_MARKER);
+
+        let mut dictionaries = Dictionaries::new();
+        dictionaries.register(dictionary);
+        let options = DecodeOptions::new().dictionaries(&dictionaries);
+        assert_eq!(value::decode_owned_with_options(&bytes, &options).unwrap(), value);
+
+        let error = value::decode_owned(&bytes).unwrap_err();
+        assert_eq!(error.reason, Reason::UnknownDictionary { id: 7, version: 1 });
+    }
+
+    #[test]
+    fn table_references() {
+        let empty = Dictionaries::empty();
+        assert_eq!(decode_symbol(&document([2, 3], &reference(0)), empty).unwrap(), b"ab");
+        assert_eq!(decode_symbol(&document([2, 3], &reference(1)), empty).unwrap(), b"c");
+        assert_eq!(decode_symbol(&document([0, 3], &reference(0)), empty).unwrap(), b"");
+
+        let error = decode_symbol(&document([2, 3], &reference(2)), empty).unwrap_err();
+        assert_eq!(error.reason, Reason::UnknownSymbol);
+        assert_eq!(error.stage,  Stage::Payload);
+    }
+
+    #[test]
+    fn invalid_tables() {
+        let empty = Dictionaries::empty();
+        for ends in [[3, 2], [2, 4], [2, 2], [4, 3]] {
+            let error = decode_symbol(&document(ends, &reference(0)), empty).unwrap_err();
+            assert_eq!(error.reason, Reason::InvalidSymbolTable, "{:?}", ends);
+            assert_eq!(error.stage,  Stage::Preamble);
+        }
+
+        // a count with more ends than the block holds.
+        let mut buffer = document([2, 3], &reference(0));
+        buffer[2] = 9 << 2;
+        assert_eq!(decode_symbol(&buffer, empty).unwrap_err().reason, Reason::InvalidSymbolTable);
+    }
+
+    #[test]
+    fn table_offset_by_dictionary() {
+        let mut dictionaries = Dictionaries::new();
+        dictionaries.register(Arc::new(Dictionary::new(7, 1, ["x", "y", "z"])));
+
+        let with_dictionary = |index: u8| {
+            let mut buffer = vec![DICTIONARY_MARKER, 7 << 2, 1 << 2];
+            buffer.extend(document([2, 3], &reference(index)));
+            buffer
+        };
+
+        assert_eq!(decode_symbol(&with_dictionary(0), &dictionaries).unwrap(), b"x");
+        assert_eq!(decode_symbol(&with_dictionary(2), &dictionaries).unwrap(), b"z");
+        assert_eq!(decode_symbol(&with_dictionary(3), &dictionaries).unwrap(), b"ab");
+        assert_eq!(decode_symbol(&with_dictionary(4), &dictionaries).unwrap(), b"c");
+        assert_eq!(decode_symbol(&with_dictionary(5), &dictionaries).unwrap_err().reason, Reason::UnknownSymbol);
+
+        // without the dictionary section, the same index is the table's.
+        assert_eq!(decode_symbol(&document([2, 3], &reference(1)), &dictionaries).unwrap(), b"c");
+    }
+
+
+    // a `Null` with the tags `symbols`, whose values are their indices.
+    fn tagged(symbols: &[&[u8]]) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        let mut builder = encoder.value().with_tags();
+        for (index, symbol) in symbols.iter().enumerate() {
+            builder = builder.tag(symbol, |e| e.write_unsigned(index as u128));
+        }
+        builder.payload_null();
+        encoder.build().unwrap()
+    }
+
+    fn index(value: Option<Value>) -> Option<u64> {
+        value.map(|value| value.as_u64().unwrap())
+    }
+
+    #[test]
+    fn tag_lookup() {
+        let bytes = tagged(&[b"a", b"b", b"a"]);
+        let value = decode_document(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(index(value.tag(b"a").unwrap()), Some(0));
+        assert_eq!(index(value.tag(b"b").unwrap()), Some(1));
+        assert_eq!(index(value.tag(b"c").unwrap()), None);
+
+        let bytes = tagged(&[]);
+        let value = decode_document(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(index(value.tag(b"a").unwrap()), None);
+
+        let mut encoder = Encoder::default();
+        encoder.write_null();
+        let bytes = encoder.build().unwrap();
+        let value = decode_document(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(index(value.tag(b"a").unwrap()), None);
+        assert!(value.tags_map().unwrap().is_empty());
+    }
+
+    #[test]
+    fn tag_maps() {
+        // below and above `LINEAR_MAX`.
+        let few:  Vec<&[u8]> = vec![b"a", b"b", b"a"];
+        let many: Vec<&[u8]> = vec![b"a", b"b", b"c", b"d", b"e", b"f", b"g", b"h", b"i", b"a"];
+        for symbols in [few, many] {
+            let last = symbols.len() as u64 - 1;
+            let bytes = tagged(&symbols);
+            let value = decode_document(&mut Reader::new(&bytes)).unwrap();
+
+            let first = value.tags_map().unwrap();
+            assert_eq!(first.len(), symbols.len() - 1);
+            assert_eq!(index(first.get(b"a")), Some(0));
+            assert_eq!(index(first.get(b"b")), Some(1));
+            assert_eq!(index(first.get(b"z")), None);
+            assert!(first.contains(b"b") && !first.contains(b"z"));
+            assert_eq!(first.iter().map(|(symbol, _)| symbol).collect::<Vec<_>>(), symbols[..symbols.len() - 1]);
+
+            let map = value.tags_map_with(Duplicates::Last).unwrap();
+            assert_eq!(index(map.get(b"a")), Some(last));
+            assert_eq!(map.iter().next().map(|(symbol, _)| symbol), Some(&b"a"[..]));
+
+            let error = value.tags_map_with(Duplicates::Reject).err().unwrap();
+            assert_eq!(error.reason, Reason::DuplicateTag);
+            assert_eq!(error.path, Path(vec![PathSegment::Tag(b"a".to_vec())]));
+
+            let unique = tagged(&symbols[..symbols.len() - 1]);
+            let value = decode_document(&mut Reader::new(&unique)).unwrap();
+            assert_eq!(value.tags_map_with(Duplicates::Reject).unwrap().len(), symbols.len() - 1);
+        }
+    }
+
+    #[test]
+    fn nested_offsets() {
+        let mut encoder = Encoder::default();
+        encoder.value().payload_list(2, |e| {
+            e.write_null();
+            e.value().with_tags()
+                .tag(b"alpha", |e| e.write_bool(true))
+                .tag(b"alpha", |e| e.write_bool(false))
+                .payload_null();
+        });
+        let bytes = encoder.build().unwrap();
+        let second = bytes.windows(5).enumerate()
+            .filter(|(_, window)| *window == b"alpha")
+            .nth(1).unwrap().0;
+
+        let root = decode_document(&mut Reader::new(&bytes)).unwrap();
+        let list = match root.payload {
+            Payload::List (list) => list,
+            _ => panic!("not a list"),
+        };
+        let value = ListDecoder::new(list, root.document, root.depth).unwrap().nth(1).unwrap();
+
+        // relative to the document, like `validate`, not to the tags block.
+        let error = value.tags_map_with(Duplicates::Reject).err().unwrap();
+        assert_eq!(error.reason, Reason::DuplicateTag);
+        assert_eq!(error.offset, second - 1);
+        let options = crate::ValidationOptions::new().tags(crate::TagPolicy::RejectDuplicates);
+        assert_eq!(crate::validate_with_options(&bytes, &options).unwrap_err().offset, error.offset);
+    }
+
+
+    // `depth` lists, each holding the next. the innermost holds a `Null`.
+    fn nested_lists(depth: usize) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        for _ in 0..depth {
+            encoder.begin_list(1);
+        }
+        encoder.write_null();
+        for _ in 0..depth {
+            encoder.end_list().unwrap();
+        }
+        encoder.build().unwrap()
+    }
+
+    // `depth` values with a tag `a`, each holding the next.
+    fn nested_tags(depth: usize) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        for _ in 0..depth {
+            encoder.append_byte(WireType::Null as u8 | WIRE_FLAG_TAGS);
+            encoder.begin_tags(1);
+            encoder.append_symbol(b"a");
+        }
+        encoder.write_null();
+        for _ in 0..depth {
+            encoder.end_tags().unwrap();
+        }
+        encoder.build().unwrap()
+    }
+
+    // a list type for the typed decoders.
+    #[allow(dead_code)]
+    struct Nested(Vec<Option<Nested>>);
+
+    impl<'de> crate::codec::Decode<'de> for Nested {
+        fn decode(value: &Value<'de>) -> Result<Nested, DecodeError> {
+            Ok(Nested(crate::codec::Decode::decode(value)?))
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    impl<'de> serde::Deserialize<'de> for Nested {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Nested, D::Error> {
+            Ok(Nested(serde::Deserialize::deserialize(deserializer)?))
+        }
+    }
+
+    // the results of every decoder for `bytes`.
+    fn decode_nested(bytes: &[u8], options: &DecodeOptions) -> Vec<Result<(), DecodeError>> {
+        let mut result = vec![
+            crate::validate_with_options(bytes, &crate::ValidationOptions::new().max_depth(options.max_depth)),
+            value::decode_owned_with_options(bytes, options).map(|_| ()),
+        ];
+
+        result.push(crate::codec::decode_with_options::<Nested>(bytes, options).map(|_| ()));
+        result.push(crate::codec::decode_with_options::<OwnedValue>(bytes, options).map(|_| ()));
+
+        #[cfg(feature = "serde")]
+        result.push(crate::de::from_slice_with_options::<Nested>(bytes, options).map(|_| ()).map_err(|error| match error {
+            crate::de::Error::Decode (error) => error,
+            error => panic!("unexpected error {:?}", error),
+        }));
+        result
+    }
+
+    fn check_depth_limit(results: Vec<Result<(), DecodeError>>, max_depth: usize, path: &Path) {
+        for result in results {
+            let error = result.unwrap_err();
+            assert_eq!(error.reason, Reason::DepthLimit);
+            assert_eq!((error.expected, error.available), (max_depth, max_depth + 1));
+            assert_eq!(&error.path, path);
+        }
+    }
+
+    #[test]
+    fn depth_limits() {
+        let max_depth = DecodeOptions::DEFAULT_MAX_DEPTH;
+        let options = DecodeOptions::new();
+        let path = Path(vec![PathSegment::Index(0); max_depth]);
+
+        // the innermost list is at depth `max_depth - 1`.
+        for result in decode_nested(&nested_lists(max_depth), &options) {
+            result.unwrap();
+        }
+        check_depth_limit(decode_nested(&nested_lists(max_depth + 1), &options), max_depth, &path);
+
+        // well past the limit, the decoders stop before they recurse.
+        check_depth_limit(decode_nested(&nested_lists(100_000), &options), max_depth, &path);
+
+        crate::validate(&nested_tags(max_depth)).unwrap();
+        value::decode_owned(&nested_tags(max_depth)).unwrap();
+        let path = Path(vec![PathSegment::Tag(b"a".to_vec()); max_depth]);
+        for bytes in [nested_tags(max_depth + 1), nested_tags(100_000)] {
+            let results = vec![crate::validate(&bytes), value::decode_owned(&bytes).map(|_| ())];
+            check_depth_limit(results, max_depth, &path);
+        }
+    }
+
+    #[test]
+    fn custom_depth_limits() {
+        let options = DecodeOptions::new().max_depth(5);
+        for result in decode_nested(&nested_lists(5), &options) {
+            result.unwrap();
+        }
+        check_depth_limit(decode_nested(&nested_lists(6), &options), 5, &Path(vec![PathSegment::Index(0); 5]));
+
+        // only the root, which isn't a list.
+        let options = DecodeOptions::new().max_depth(0);
+        crate::validate_with_options(&nested_lists(0), &crate::ValidationOptions::new().max_depth(0)).unwrap();
+        value::decode_owned_with_options(&nested_lists(0), &options).unwrap();
+        check_depth_limit(decode_nested(&nested_lists(1), &options), 0, &Path::default());
+
+        let options = DecodeOptions::new().max_depth(200);
+        for result in decode_nested(&nested_lists(200), &options) {
+            result.unwrap();
+        }
+    }
+
+
+    // decodes the document `write` builds, and passes its root to `check`.
+    fn number<W: FnOnce(&mut Encoder), C: FnOnce(&Value)>(write: W, check: C) {
+        let mut encoder = Encoder::default();
+        write(&mut encoder);
+        let bytes = encoder.build().unwrap();
+        check(&decode_document(&mut Reader::new(&bytes)).unwrap());
+    }
+
+    #[test]
+    fn fixed_width_numbers() {
+        number(|e| e.write_nat8(u8::MAX), |v| {
+            assert_eq!(v.as_u64(), Ok(u8::MAX as u64));
+            assert_eq!(v.as_i64(), Ok(u8::MAX as i64));
+        });
+        number(|e| e.write_nat16(u16::MAX), |v| assert_eq!(v.as_u128(), Ok(u16::MAX as u128)));
+        number(|e| e.write_nat32(u32::MAX), |v| assert_eq!(v.as_i128(), Ok(u32::MAX as i128)));
+        number(|e| e.write_nat64(u64::MAX), |v| {
+            assert_eq!(v.as_u64(), Ok(u64::MAX));
+            assert_eq!(v.as_i64(), Err(NumberError::OutOfRange(WireType::Nat64)));
+            assert_eq!(v.as_i128(), Ok(u64::MAX as i128));
+        });
+
+        number(|e| e.write_int8(i8::MIN), |v| {
+            assert_eq!(v.as_i64(), Ok(i8::MIN as i64));
+            assert_eq!(v.as_u64(), Err(NumberError::OutOfRange(WireType::Int8)));
+        });
+        number(|e| e.write_int16(i16::MIN), |v| assert_eq!(v.as_i128(), Ok(i16::MIN as i128)));
+        number(|e| e.write_int32(i32::MAX), |v| assert_eq!(v.as_u64(), Ok(i32::MAX as u64)));
+        number(|e| e.write_int64(i64::MIN), |v| {
+            assert_eq!(v.as_i64(), Ok(i64::MIN));
+            assert_eq!(v.as_u128(), Err(NumberError::OutOfRange(WireType::Int64)));
+        });
+        number(|e| e.write_int64(-1), |v| assert_eq!(v.as_u64(), Err(NumberError::OutOfRange(WireType::Int64))));
+
+        number(|e| e.write_string("1"), |v| {
+            assert_eq!(v.as_u64(), Err(NumberError::UnexpectedType(WireType::String)));
+            assert_eq!(v.as_f64(), Err(NumberError::UnexpectedType(WireType::String)));
+        });
+        number(|e| e.write_f32(1.5), |v| {
+            assert_eq!(v.as_f64(), Ok(1.5));
+            assert_eq!(v.as_i64(), Err(NumberError::UnexpectedType(WireType::Float32)));
+        });
+    }
+
+    #[test]
+    fn variable_width_numbers() {
+        number(|e| e.write_nat(&[1, 2]), |v| {
+            assert_eq!(v.as_u64(), Ok(0x0201));
+            assert_eq!(v.as_i64(), Ok(0x0201));
+        });
+        number(|e| e.write_int(&[0xff]), |v| {
+            assert_eq!(v.as_i64(), Ok(-1));
+            assert_eq!(v.as_u64(), Err(NumberError::OutOfRange(WireType::Int)));
+        });
+        number(|e| e.write_int(&[0x00, 0x80]), |v| assert_eq!(v.as_i64(), Ok(-0x8000)));
+
+        number(|e| e.write_unsigned(u64::MAX as u128 + 1), |v| {
+            assert_eq!(v.as_u128(), Ok(u64::MAX as u128 + 1));
+            assert_eq!(v.as_u64(), Err(NumberError::OutOfRange(WireType::Nat)));
+        });
+        number(|e| e.write_signed(i64::MIN as i128 - 1), |v| {
+            assert_eq!(v.as_i128(), Ok(i64::MIN as i128 - 1));
+            assert_eq!(v.as_i64(), Err(NumberError::OutOfRange(WireType::Int)));
+        });
+
+        // 2^128 and -2^127 - 1 overflow the 128 bit accessors.
+        let mut nat = [0; 17];
+        nat[16] = 1;
+        number(|e| e.write_nat(&nat), |v| {
+            assert_eq!(v.as_u128(), Err(NumberError::OutOfRange(WireType::Nat)));
+            assert_eq!(v.as_i128(), Err(NumberError::OutOfRange(WireType::Nat)));
+        });
+        let mut int = [0xff; 17];
+        int[15] = 0x7f;
+        number(|e| e.write_int(&int), |v| {
+            assert_eq!(v.as_i128(), Err(NumberError::OutOfRange(WireType::Int)));
+            assert_eq!(v.as_u128(), Err(NumberError::OutOfRange(WireType::Int)));
+            assert_eq!(v.as_f64(), Err(NumberError::OutOfRange(WireType::Int)));
+        });
+    }
+
+    #[test]
+    fn numbers_as_f64() {
+        number(|e| e.write_unsigned(1 << 53), |v| assert_eq!(v.as_f64(), Ok(2f64.powi(53))));
+        number(|e| e.write_unsigned((1 << 53) + 1), |v| assert_eq!(v.as_f64(), Err(NumberError::OutOfRange(WireType::Nat64))));
+        number(|e| e.write_signed(-(1 << 53)), |v| assert_eq!(v.as_f64(), Ok(-2f64.powi(53))));
+        number(|e| e.write_nat64(u64::MAX), |v| assert_eq!(v.as_f64(), Err(NumberError::OutOfRange(WireType::Nat64))));
+
+        // `as` saturates to 2^127 and 2^128, which are one past the maximum.
+        number(|e| e.write_signed(i128::MAX), |v| assert_eq!(v.as_f64(), Err(NumberError::OutOfRange(WireType::Int))));
+        number(|e| e.write_unsigned(u128::MAX), |v| assert_eq!(v.as_f64(), Err(NumberError::OutOfRange(WireType::Nat))));
+        number(|e| e.write_signed(i128::MIN), |v| assert_eq!(v.as_f64(), Ok(-2f64.powi(127))));
+        number(|e| e.write_unsigned(1 << 127), |v| assert_eq!(v.as_f64(), Ok(2f64.powi(127))));
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::HashMap, sync::Arc};
+
+
+// a pre-agreed list of symbols, identified by `id` and `version`.
+// documents encoded against it reference its symbols by index instead of
+// carrying them, see `DICTIONARY_MARKER`. a dictionary must not change once
+// documents were encoded against it, publish a new version instead.
+#[derive(Debug, Clone)]
+pub struct Dictionary {
+    id:      u32,
+    version: u32,
+    symbols: Vec<Vec<u8>>,
+    indices: HashMap<Vec<u8>, u32>,
+}
+
+impl Dictionary {
+    // note: a duplicate symbol is encoded with the index of its first
+    // occurrence. every index still decodes to its symbol.
+    pub fn new<I, S>(id: u32, version: u32, symbols: I) -> Dictionary
+        where I: IntoIterator<Item = S>, S: AsRef<[u8]>
+    {
+        let symbols: Vec<Vec<u8>> = symbols.into_iter().map(|symbol| symbol.as_ref().to_vec()).collect();
+
+        let mut indices = HashMap::with_capacity(symbols.len());
+        for (index, symbol) in symbols.iter().enumerate() {
+            indices.entry(symbol.clone()).or_insert(index as u32);
+        }
+
+        Dictionary { id, version, symbols, indices }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        self.symbols.get(index).map(|symbol| &symbol[..])
+    }
+
+    pub fn index_of(&self, symbol: &[u8]) -> Option<u32> {
+        self.indices.get(symbol).copied()
+    }
+}
+
+
+// the dictionaries a decoder accepts, by id and version.
+#[derive(Debug, Default)]
+pub struct Dictionaries {
+    dictionaries: Vec<Arc<Dictionary>>,
+}
+
+impl Dictionaries {
+    pub const fn new() -> Dictionaries {
+        Dictionaries { dictionaries: Vec::new() }
+    }
+
+    // used by the decoding functions that take no dictionaries.
+    pub fn empty() -> &'static Dictionaries {
+        static EMPTY: Dictionaries = Dictionaries::new();
+        &EMPTY
+    }
+
+    // replaces a dictionary with the same id and version, documents decode
+    // against the one registered last.
+    pub fn register(&mut self, dictionary: Arc<Dictionary>) {
+        match self.dictionaries.iter_mut().find(|d| d.id == dictionary.id && d.version == dictionary.version) {
+            Some(entry) => *entry = dictionary,
+            None        => self.dictionaries.push(dictionary),
+        }
+    }
+
+    pub fn get(&self, id: u32, version: u32) -> Option<&Dictionary> {
+        self.dictionaries.iter()
+            .find(|d| d.id == id && d.version == version)
+            .map(|d| &**d)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{Encoder, EncoderOptions};
+    use crate::decoder::{DecodeOptions, Payload};
+    use crate::wire_type::WireType;
+    use slice_reader::Reader;
+
+    // decodes a document with `dictionaries`, whose root is a symbol.
+    fn decode_symbol(bytes: &[u8], dictionaries: &Dictionaries) -> Vec<u8> {
+        let options = DecodeOptions::new().dictionaries(dictionaries);
+        let value = crate::decoder::decode_document_with_options(&mut Reader::new(bytes), &options).unwrap();
+        match value.payload {
+            Payload::Symbol (symbol) => symbol.to_vec(),
+            _ => panic!("not a symbol"),
+        }
+    }
+
+    #[test]
+    fn duplicate_symbols() {
+        let dictionary = Arc::new(Dictionary::new(1, 1, ["a", "b", "a"]));
+        assert_eq!(dictionary.len(), 3);
+        assert_eq!(dictionary.index_of(b"a"), Some(0));
+        assert_eq!(dictionary.index_of(b"b"), Some(1));
+        assert_eq!(dictionary.get(2), Some(&b"a"[..]));
+        assert_eq!(dictionary.get(3), None);
+
+        let mut encoder = Encoder::new(EncoderOptions::new().dictionary(dictionary.clone())).unwrap();
+        encoder.write_symbol(b"a");
+        let bytes = encoder.build().unwrap();
+        assert_eq!(bytes[bytes.len() - 2..], [WireType::Symbol as u8, 0]);
+
+        // a reference to the second occurrence, as another encoder may write.
+        let mut other = bytes.clone();
+        *other.last_mut().unwrap() = 2 << 3;
+
+        let mut dictionaries = Dictionaries::new();
+        dictionaries.register(dictionary);
+        assert_eq!(decode_symbol(&bytes, &dictionaries), b"a");
+        assert_eq!(decode_symbol(&other, &dictionaries), b"a");
+    }
+
+    #[test]
+    fn register() {
+        let mut dictionaries = Dictionaries::new();
+        dictionaries.register(Arc::new(Dictionary::new(1, 1, ["a"])));
+        dictionaries.register(Arc::new(Dictionary::new(1, 2, ["b"])));
+        dictionaries.register(Arc::new(Dictionary::new(2, 1, ["c"])));
+        assert_eq!(dictionaries.get(1, 1).unwrap().get(0), Some(&b"a"[..]));
+        assert_eq!(dictionaries.get(1, 2).unwrap().get(0), Some(&b"b"[..]));
+        assert_eq!(dictionaries.get(2, 1).unwrap().get(0), Some(&b"c"[..]));
+        assert!(dictionaries.get(2, 2).is_none());
+
+        // the same id and version replaces the earlier dictionary.
+        dictionaries.register(Arc::new(Dictionary::new(1, 1, ["x", "y"])));
+        assert_eq!(dictionaries.dictionaries.len(), 3);
+        let dictionary = dictionaries.get(1, 1).unwrap();
+        assert_eq!(dictionary.get(0), Some(&b"x"[..]));
+        assert_eq!(dictionary.len(), 2);
+
+        let mut encoder = Encoder::new(EncoderOptions::new().dictionary(Arc::new(Dictionary::new(1, 1, ["a"])))).unwrap();
+        encoder.write_symbol(b"a");
+        assert_eq!(decode_symbol(&encoder.build().unwrap(), &dictionaries), b"x");
+    }
+}
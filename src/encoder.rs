@@ -1,10 +1,103 @@
+use std::{collections::HashMap, io::{self, Write}, sync::Arc};
 use slice_reader::{Reader, byte_order::aliases::{LE, NE}};
-use crate::utils::*;
+use crate::{wire_type::*, utils::*, decimal::{Decimal32, Decimal64}, dictionary::Dictionary};
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
-    SizeOverflow,
+    InvalidSizeMaxBytes(usize),
+    UnbalancedSize,
+    CountMismatch { expected: usize, written: usize },
+    // a `ValueBuilder` was dropped before a payload was written.
+    MissingPayload,
+    // a kind was written twice, or after tags.
+    MisplacedKind,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::InvalidSizeMaxBytes (size_max_bytes) =>
+                write!(f, "invalid size_max_bytes {}, expected 1, 2, 4, or 8", size_max_bytes),
+            Error::UnbalancedSize =>
+                write!(f, "unbalanced size, list, or tags block"),
+            Error::CountMismatch { expected, written } =>
+                write!(f, "expected {} values, {} were written", expected, written),
+            Error::MissingPayload =>
+                write!(f, "value builder dropped without a payload"),
+            Error::MisplacedKind =>
+                write!(f, "kind written twice, or after tags"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+
+#[derive(Debug, Clone)]
+pub struct EncoderOptions {
+    size_max_bytes: usize,
+    compress_sizes: bool,
+    capacity:       usize,
+    checked:        bool,
+    intern_symbols: bool,
+    dictionary:     Option<Arc<Dictionary>>,
+}
+
+impl EncoderOptions {
+    pub fn new() -> EncoderOptions {
+        EncoderOptions {
+            size_max_bytes: 8,
+            compress_sizes: true,
+            capacity:       0,
+            checked:        false,
+            intern_symbols: false,
+            dictionary:     None,
+        }
+    }
+
+    // the width of size placeholders: 1, 2, 4, or 8.
+    // larger sizes are widened on build.
+    pub fn size_max_bytes(self, size_max_bytes: usize) -> EncoderOptions {
+        EncoderOptions { size_max_bytes, ..self }
+    }
+
+    pub fn compress_sizes(self, compress_sizes: bool) -> EncoderOptions {
+        EncoderOptions { compress_sizes, ..self }
+    }
+
+    // initial buffer capacity in bytes.
+    pub fn capacity(self, capacity: usize) -> EncoderOptions {
+        EncoderOptions { capacity, ..self }
+    }
+
+    // in checked mode, `end_list` and `end_tags` verify the number of values
+    // written against the declared length and return the mismatch.
+    // `ValueBuilder::tag` verifies that its closure writes one value, and
+    // `payload_list` that its closure writes `length`. these errors are
+    // reported by `build`.
+    pub fn checked(self, checked: bool) -> EncoderOptions {
+        EncoderOptions { checked, ..self }
+    }
+
+    // symbols that are used more than once are written to the document's
+    // symbol table, and referenced by index after their first use.
+    // off by default, as older decoders reject symbol tables.
+    pub fn intern_symbols(self, intern_symbols: bool) -> EncoderOptions {
+        EncoderOptions { intern_symbols, ..self }
+    }
+
+    // symbols in `dictionary` are referenced by index. the decoder needs a
+    // dictionary with the same id and version.
+    pub fn dictionary(self, dictionary: Arc<Dictionary>) -> EncoderOptions {
+        EncoderOptions { dictionary: Some(dictionary), ..self }
+    }
+}
+
+impl Default for EncoderOptions {
+    fn default() -> EncoderOptions {
+        EncoderOptions::new()
+    }
 }
 
 
@@ -17,15 +110,43 @@ pub struct Encoder {
 
     size_max_bytes: usize,
     compress_sizes: bool,
-    size_overflow: bool,
+
+    // sizes that don't fit into `size_max_bytes`, sorted by offset.
+    // they are written with their full width by `build`.
+    widened: Vec<(usize, u64)>,
+
+    // the count placeholders of the open deferred lists, innermost last.
+    deferred: Vec<usize>,
+
+    // the number of values started outside of any list or tags block.
+    roots: usize,
+
+    // checked mode only.
+    checked:  bool,
+    counters: Vec<Counter>,
+    error:    Option<Error>,
+
+    // `None` for symbols that were used once, those stay inline.
+    intern_symbols: bool,
+    symbols:        HashMap<Vec<u8>, Option<u32>>,
+    symbol_bytes:   Vec<u8>,
+    symbol_ends:    Vec<u32>,
+
+    // the dictionary section is only written if the document uses it.
+    dictionary:      Option<Arc<Dictionary>>,
+    dictionary_used: bool,
 }
 
 impl Encoder {
-    pub fn new(size_max_bytes: usize, compress_sizes: bool) -> Encoder {
-        match size_max_bytes { 1 | 2 | 4 | 8 => (), _ => unreachable!() }
+    pub fn new(options: EncoderOptions) -> Result<Encoder, Error> {
+        let EncoderOptions { size_max_bytes, compress_sizes, capacity, checked, intern_symbols, dictionary } = options;
+        match size_max_bytes {
+            1 | 2 | 4 | 8 => (),
+            _ => return Err(Error::InvalidSizeMaxBytes(size_max_bytes)),
+        }
 
-        Encoder {
-            buffer: vec![],
+        Ok(Encoder {
+            buffer: Vec::with_capacity(capacity),
             sizers: vec![ Sizer { offset: 0, size: 0 } ],
 
             size_offsets: vec![],
@@ -33,7 +154,60 @@ impl Encoder {
 
             size_max_bytes,
             compress_sizes,
-            size_overflow: false,
+
+            widened: vec![],
+
+            deferred: vec![],
+
+            roots: 0,
+
+            checked,
+            counters: vec![],
+            error:    None,
+
+            intern_symbols,
+            symbols:      HashMap::new(),
+            symbol_bytes: vec![],
+            symbol_ends:  vec![],
+
+            dictionary,
+            dictionary_used: false,
+        })
+    }
+
+    pub fn with_capacity(capacity: usize) -> Encoder {
+        Encoder::new(EncoderOptions::new().capacity(capacity)).unwrap()
+    }
+
+    // clears the document, but keeps the allocations.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.sizers.clear();
+        self.sizers.push(Sizer { offset: 0, size: 0 });
+
+        self.size_offsets.clear();
+        self.last_size_offset = 0;
+
+        self.widened.clear();
+
+        self.deferred.clear();
+
+        self.roots = 0;
+
+        self.counters.clear();
+        self.error = None;
+
+        self.symbols.clear();
+        self.symbol_bytes.clear();
+        self.symbol_ends.clear();
+
+        self.dictionary_used = false;
+    }
+
+    // the first error is reported by `build`.
+    pub(crate) fn fail(&mut self, error: Error) {
+        if self.error.is_none() {
+            self.error = Some(error);
         }
     }
 
@@ -57,16 +231,260 @@ impl Encoder {
     }
 
     pub fn append_symbol(&mut self, symbol: &[u8]) {
+        let dictionary = self.dictionary.as_ref().filter(|_| !symbol.is_empty());
+        if let Some(index) = dictionary.and_then(|dictionary| dictionary.index_of(symbol)) {
+            self.dictionary_used = true;
+            self.append_size((index as u64) << 1);
+            return;
+        }
+
+        if let Some(index) = self.intern(symbol) {
+            // note: the table's indices follow the dictionary's.
+            let index = index as u64 + self.dictionary.as_ref().map_or(0, |dictionary| dictionary.len() as u64);
+            self.append_size(index << 1);
+            return;
+        }
+
         let (bytes, length) = encode_size::<LE>((symbol.len() << 1 | 1) as u64);
         self.append(&bytes[..length]);
         self.append(symbol);
     }
 
+    // the table index of `symbol`, from its second use on.
+    fn intern(&mut self, symbol: &[u8]) -> Option<u32> {
+        if !self.intern_symbols || symbol.is_empty() {
+            return None;
+        }
 
-    pub fn begin_size(&mut self) {
+        match self.symbols.get_mut(symbol) {
+            Some(Some(index)) => Some(*index),
+
+            Some(entry) => {
+                let end = u32::try_from(self.symbol_bytes.len() + symbol.len()).ok()?;
+                let index = self.symbol_ends.len() as u32;
+                self.symbol_bytes.extend(symbol);
+                self.symbol_ends.push(end);
+                *entry = Some(index);
+                Some(index)
+            },
+
+            None => {
+                self.symbols.insert(symbol.to_vec(), None);
+                None
+            },
+        }
+    }
+
+    pub fn append_size_prefixed(&mut self, bytes: &[u8]) {
+        self.append_size(bytes.len() as u64);
+        self.append(bytes);
+    }
+
+
+    pub fn value(&mut self) -> ValueBuilder<'_> {
+        if let Some(counter) = self.counters.last_mut() {
+            counter.written += 1;
+        }
+        if self.sizers.len() == 1 {
+            self.roots += 1;
+        }
+
+        let header_offset = self.buffer.len();
+        self.append_byte(0);
+        ValueBuilder {
+            encoder: Some(self),
+            header_offset,
+            flags: 0,
+            tag_count: 0,
+            tag_count_offset: 0,
+        }
+    }
+
+    pub fn write_null(&mut self) {
+        self.value().payload_null();
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.value().payload_bool(value);
+    }
+
+    pub fn write_nat8(&mut self, value: u8) {
+        self.value().payload_nat8(value);
+    }
+
+    pub fn write_nat16(&mut self, value: u16) {
+        self.value().payload_nat16(value);
+    }
+
+    pub fn write_nat32(&mut self, value: u32) {
+        self.value().payload_nat32(value);
+    }
+
+    pub fn write_nat64(&mut self, value: u64) {
+        self.value().payload_nat64(value);
+    }
+
+    pub fn write_int8(&mut self, value: i8) {
+        self.value().payload_int8(value);
+    }
+
+    pub fn write_int16(&mut self, value: i16) {
+        self.value().payload_int16(value);
+    }
+
+    pub fn write_int32(&mut self, value: i32) {
+        self.value().payload_int32(value);
+    }
+
+    pub fn write_int64(&mut self, value: i64) {
+        self.value().payload_int64(value);
+    }
+
+    pub fn write_unsigned(&mut self, value: u128) {
+        self.value().payload_unsigned(value);
+    }
+
+    pub fn write_signed(&mut self, value: i128) {
+        self.value().payload_signed(value);
+    }
+
+    pub fn write_nat(&mut self, value: &[u8]) {
+        self.value().payload_nat(value);
+    }
+
+    pub fn write_int(&mut self, value: &[u8]) {
+        self.value().payload_int(value);
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.value().payload_f32(value);
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.value().payload_f64(value);
+    }
+
+    pub fn write_bytes(&mut self, value: &[u8]) {
+        self.value().payload_bytes(value);
+    }
+
+    pub fn write_string(&mut self, value: &str) {
+        self.value().payload_string(value);
+    }
+
+    pub fn write_decimal32(&mut self, value: Decimal32) {
+        self.value().payload_decimal32(value);
+    }
+
+    pub fn write_decimal64(&mut self, value: Decimal64) {
+        self.value().payload_decimal64(value);
+    }
+
+    pub fn write_symbol(&mut self, value: &[u8]) {
+        self.value().payload_symbol(value);
+    }
+
+    // note: the caller must write exactly `length` values before `end_list`.
+    pub fn begin_list(&mut self, length: usize) {
+        self.value().begin_list(length);
+    }
+
+    pub fn end_list(&mut self) -> Result<(), Error> {
+        self.end_counted()
+    }
+
+    // for lists whose length is not known up front. they must be ended by
+    // `end_list_deferred`, `end_list` fails with `Error::UnbalancedSize`.
+    pub fn begin_list_deferred(&mut self) -> DeferredLength {
+        self.value().begin_list_deferred()
+    }
+
+    pub fn end_list_deferred(&mut self, deferred: DeferredLength, length: usize) -> Result<(), Error> {
+        self.end_deferred(deferred.offset, length)
+    }
+
+    // for values with a raw `WIRE_FLAG_TAGS` header. the caller must write
+    // exactly `length` symbol and value pairs before `end_tags`.
+    pub fn begin_tags(&mut self, length: usize) {
+        self.begin_counted(Some(length));
+    }
+
+    pub fn end_tags(&mut self) -> Result<(), Error> {
+        self.end_counted()
+    }
+
+    fn begin_counted(&mut self, length: Option<usize>) {
+        self.begin_size();
+        if let Some(length) = length {
+            self.append_size(length as u64);
+        }
+
+        if self.checked {
+            self.counters.push(Counter {
+                expected: length,
+                written:  0,
+                depth:    self.sizers.len(),
+            });
+        }
+    }
+
+    fn end_counted(&mut self) -> Result<(), Error> {
+        // note: a deferred list must be ended by `end_deferred`, its count
+        // placeholder would stay unpatched otherwise.
+        if let Some(&offset) = self.deferred.last() {
+            if offset > self.sizers.last().unwrap().offset {
+                return Err(Error::UnbalancedSize);
+            }
+        }
+
+        // note: the counter is only popped once the block is known to be
+        // its own, a failed call leaves the open blocks as they were.
+        if self.checked {
+            match self.counters.last() {
+                Some(counter) if counter.depth == self.sizers.len() => (),
+                _ => return Err(Error::UnbalancedSize),
+            }
+        }
+
+        self.end_size()?;
+
+        let counter = if self.checked { self.counters.pop() } else { None };
+        if let Some(Counter { expected: Some(expected), written, .. }) = counter {
+            if written != expected {
+                return Err(Error::CountMismatch { expected, written });
+            }
+        }
+        Ok(())
+    }
+
+    // the length is a size placeholder, patched by `end_deferred`.
+    fn begin_deferred(&mut self) -> usize {
+        self.begin_counted(None);
+        let offset = self.reserve_size();
+        self.deferred.push(offset);
+        offset
+    }
+
+    fn end_deferred(&mut self, offset: usize, length: usize) -> Result<(), Error> {
+        // the innermost open block must be this list.
+        if self.deferred.last() != Some(&offset) || self.sizers.last().unwrap().offset > offset {
+            return Err(Error::UnbalancedSize);
+        }
+        self.deferred.pop();
+
+        let size = self.patch_size(offset, length as u64);
+        self.commit_size(size);
+
+        if let Some(counter) = self.counters.last_mut() {
+            counter.expected = Some(length);
+        }
+        self.end_counted()
+    }
+
+
+    fn reserve_size(&mut self) -> usize {
         let offset = self.buffer.len();
         self.buffer.extend((0..self.size_max_bytes).map(|_| 0));
-        self.sizers.push(Sizer { offset: offset, size: 0 });
 
         if self.compress_sizes {
             let delta = (offset - self.last_size_offset) as u64;
@@ -74,18 +492,25 @@ impl Encoder {
             self.size_offsets.extend(&delta[..length]);
             self.last_size_offset = offset;
         }
-    }
 
-    pub fn end_size(&mut self) {
-        assert!(self.sizers.len() > 1);
-        let sizer = self.sizers.pop().unwrap();
+        offset
+    }
 
-        let (size, length) = encode_size::<LE>(sizer.size as u64);
+    // returns the number of bytes the size occupies in the built document.
+    fn patch_size(&mut self, offset: usize, value: u64) -> usize {
+        let (size, length) = encode_size::<LE>(value);
         if length > self.size_max_bytes {
-            self.size_overflow = true;
+            let index = self.widened.partition_point(|(other, _)| *other < offset);
+            self.widened.insert(index, (offset, value));
+            return length;
         }
 
-        let offset = sizer.offset;
+        // note: compression keeps the minimal encoding, so only
+        // uncompressed sizes need to be padded to the full width.
+        let size =
+            if self.compress_sizes { size }
+            else { encode_size_fixed::<LE>(value, self.size_max_bytes) };
+
         match self.size_max_bytes {
             1 => self.buffer[offset..offset + 1].copy_from_slice(&size[0..1]),
             2 => self.buffer[offset..offset + 2].copy_from_slice(&size[0..2]),
@@ -94,27 +519,114 @@ impl Encoder {
             _ => unreachable!()
         }
 
-        if self.compress_sizes {
-            self.commit_size(length + sizer.size);
+        if self.compress_sizes { length } else { self.size_max_bytes }
+    }
+
+    pub fn begin_size(&mut self) {
+        let offset = self.reserve_size();
+        self.sizers.push(Sizer { offset, size: 0 });
+    }
+
+    pub fn end_size(&mut self) -> Result<(), Error> {
+        if self.sizers.len() < 2 {
+            return Err(Error::UnbalancedSize);
         }
-        else {
-            self.commit_size(self.size_max_bytes + sizer.size);
+
+        let sizer = self.sizers.pop().unwrap();
+        let length = self.patch_size(sizer.offset, sizer.size as u64);
+        self.commit_size(length + sizer.size);
+        Ok(())
+    }
+
+    pub fn size(&self) -> Result<usize, Error> {
+        if self.sizers.len() != 1 {
+            return Err(Error::UnbalancedSize);
+        }
+        Ok(self.preamble_size() + self.sizers[0].size)
+    }
+
+    fn preamble_size(&self) -> usize {
+        self.dictionary_size() + self.symbol_table_size()
+    }
+
+    // the dictionary section is needed when a symbol references the
+    // dictionary, or the table, whose indices are offset by it.
+    fn used_dictionary(&self) -> Option<&Dictionary> {
+        self.dictionary.as_deref().filter(|_| self.dictionary_used || !self.symbol_ends.is_empty())
+    }
+
+    fn dictionary_size(&self) -> usize {
+        match self.used_dictionary() {
+            Some(dictionary) => {
+                let (_, id)      = encode_size::<LE>(dictionary.id() as u64);
+                let (_, version) = encode_size::<LE>(dictionary.version() as u64);
+                1 + id + version
+            },
+            None => 0,
         }
     }
 
-    pub fn size(&self) -> usize {
-        assert!(self.sizers.len() == 1);
-        self.sizers[0].size
+    fn write_dictionary<W: Write>(&self, dest: &mut W) -> io::Result<()> {
+        if let Some(dictionary) = self.used_dictionary() {
+            let (id, id_length)           = encode_size::<LE>(dictionary.id() as u64);
+            let (version, version_length) = encode_size::<LE>(dictionary.version() as u64);
+
+            dest.write_all(&[DICTIONARY_MARKER])?;
+            dest.write_all(&id[..id_length])?;
+            dest.write_all(&version[..version_length])?;
+        }
+        Ok(())
     }
 
-    fn compress(&self, dest: &mut Vec<u8>) {
-        let size = self.size();
+    fn symbol_table_size(&self) -> usize {
+        if self.symbol_ends.is_empty() {
+            return 0;
+        }
+        let (_, count) = encode_size::<LE>(self.symbol_ends.len() as u64);
+        let size = count + 4*self.symbol_ends.len() + self.symbol_bytes.len();
+        let (_, prefix) = encode_size::<LE>(size as u64);
+        1 + prefix + size
+    }
+
+    fn write_symbol_table<W: Write>(&self, dest: &mut W) -> io::Result<()> {
+        if self.symbol_ends.is_empty() {
+            return Ok(());
+        }
+        let (count, count_length) = encode_size::<LE>(self.symbol_ends.len() as u64);
+        let size = count_length + 4*self.symbol_ends.len() + self.symbol_bytes.len();
+        let (prefix, prefix_length) = encode_size::<LE>(size as u64);
 
-        let old_length = dest.len();
-        dest.reserve(size);
+        dest.write_all(&[SYMBOL_TABLE_MARKER])?;
+        dest.write_all(&prefix[..prefix_length])?;
+        dest.write_all(&count[..count_length])?;
+        for end in &self.symbol_ends {
+            dest.write_all(&end.to_le_bytes())?;
+        }
+        dest.write_all(&self.symbol_bytes)
+    }
+
+    pub(crate) fn check_build(&self) -> Result<(), Error> {
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+        if self.sizers.len() != 1 {
+            return Err(Error::UnbalancedSize);
+        }
+        Ok(())
+    }
+
+    // note: only values started by `value` are counted, not raw headers.
+    pub(crate) fn roots(&self) -> usize {
+        self.roots
+    }
+
+    fn compress<W: Write>(&self, dest: &mut W) -> io::Result<()> {
+        let size = self.sizers[0].size;
+        let mut written = 0;
 
         let mut buffer  = Reader::new(&self.buffer);
         let mut offsets = Reader::new(&self.size_offsets);
+        let mut widened = self.widened.iter().peekable();
         let mut first = true;
 
         while buffer.has_some() {
@@ -132,60 +644,703 @@ impl Encoder {
                     next_size -= self.size_max_bytes;
                 }
 
-                dest.extend(buffer.next_n(next_size).unwrap());
+                dest.write_all(buffer.next_n(next_size).unwrap())?;
+                written += next_size;
 
-                let (_size, length) = peek_decode_size::<LE>(&buffer).unwrap();
-                dest.extend(buffer.peek_n(length).unwrap());
+                if let Some((_, value)) = widened.next_if(|(offset, _)| *offset == buffer.cursor) {
+                    let (size, length) = encode_size::<LE>(*value);
+                    dest.write_all(&size[..length])?;
+                    written += length;
+                }
+                else {
+                    let (_size, length) = peek_decode_size::<LE>(&buffer).unwrap();
+                    dest.write_all(buffer.peek_n(length).unwrap())?;
+                    written += length;
+                }
                 buffer.next_n(self.size_max_bytes).unwrap();
             }
             else {
-                dest.extend(buffer.rest());
+                let rest = buffer.rest();
+                dest.write_all(rest)?;
+                written += rest.len();
                 break;
             }
         }
-        assert_eq!(dest.len() - old_length, size);
+        assert_eq!(written, size);
+        Ok(())
+    }
+
+    fn widen<W: Write>(&self, dest: &mut W) -> io::Result<()> {
+        let mut written = 0;
+
+        let mut cursor = 0;
+        for (offset, value) in &self.widened {
+            dest.write_all(&self.buffer[cursor..*offset])?;
+            let (size, length) = encode_size::<LE>(*value);
+            dest.write_all(&size[..length])?;
+            written += offset - cursor + length;
+            cursor = offset + self.size_max_bytes;
+        }
+        dest.write_all(&self.buffer[cursor..])?;
+        written += self.buffer.len() - cursor;
+
+        assert_eq!(written, self.sizers[0].size);
+        Ok(())
     }
 
     pub fn build(self) -> Result<Vec<u8>, Error> {
-        assert!(self.sizers.len() == 1);
-        if self.size_overflow {
-            return Err(Error::SizeOverflow);
+        if !self.compress_sizes && self.widened.is_empty() && self.preamble_size() == 0 {
+            self.check_build()?;
+            return Ok(self.buffer);
         }
 
+        let mut result = Vec::with_capacity(self.preamble_size() + self.sizers[0].size);
+        self.build_append(&mut result)?;
+        Ok(result)
+    }
+
+    // like `build_append`, but leaves the encoder ready for the next
+    // document, even if building fails.
+    pub fn build_into(&mut self, dest: &mut Vec<u8>) -> Result<(), Error> {
+        let result = self.build_append(dest);
+        self.reset();
+        result
+    }
+
+    pub fn build_append(&self, dest: &mut Vec<u8>) -> Result<(), Error> {
+        self.check_build()?;
+
+        dest.reserve(self.preamble_size() + self.sizers[0].size);
+        // note: writing to a vec can't fail.
+        self.write_document(dest).unwrap();
+        Ok(())
+    }
+
+    // writes the document, which must pass `check_build`, in pieces: the
+    // sizes are compressed or widened on the way, the encoder's buffer isn't
+    // copied.
+    pub(crate) fn write_document<W: Write>(&self, dest: &mut W) -> io::Result<()> {
+        self.write_dictionary(dest)?;
+        self.write_symbol_table(dest)?;
         if self.compress_sizes {
-            let mut result = vec![];
-            self.compress(&mut result);
-            Ok(result)
+            self.compress(dest)
+        }
+        else if !self.widened.is_empty() {
+            self.widen(dest)
         }
         else {
-            Ok(self.buffer)
+            dest.write_all(&self.buffer)
         }
     }
+}
 
-    pub fn build_append(&self, dest: &mut Vec<u8>) -> Result<(), Error> {
-        assert!(self.sizers.len() == 1);
-        if self.size_overflow {
-            return Err(Error::SizeOverflow);
+impl Default for Encoder {
+    fn default() -> Encoder {
+        Encoder::new(EncoderOptions::default()).unwrap()
+    }
+}
+
+
+// sections are written in wire order: header, kind, tags, payload.
+// the header byte is reserved up front and patched once the payload type is
+// known. the tag count is a size placeholder, patched when the tags end.
+// misuse is recorded and reported by `build`: a builder dropped without a
+// payload leaves its header unpatched.
+#[must_use = "a value needs a payload"]
+pub struct ValueBuilder<'enc> {
+    // `None` once the payload was written.
+    encoder: Option<&'enc mut Encoder>,
+    header_offset: usize,
+    flags: u8,
+    tag_count: usize,
+    tag_count_offset: usize,
+}
+
+impl<'enc> ValueBuilder<'enc> {
+    fn encoder(&mut self) -> &mut Encoder {
+        self.encoder.as_deref_mut().unwrap()
+    }
+
+    pub fn kind(mut self, kind: &[u8]) -> Self {
+        if self.flags != 0 {
+            self.encoder().fail(Error::MisplacedKind);
+            return self;
         }
+        self.flags |= WIRE_FLAG_KIND;
+        self.encoder().append_symbol(kind);
+        self
+    }
 
-        if self.compress_sizes {
-            self.compress(dest);
+    // starts the tags section, even if no tags follow.
+    pub fn with_tags(mut self) -> Self {
+        if self.flags & WIRE_FLAG_TAGS == 0 {
+            self.flags |= WIRE_FLAG_TAGS;
+            self.tag_count_offset = self.encoder().begin_deferred();
+        }
+        self
+    }
+
+    // `value` must write exactly one value.
+    pub fn tag<F: FnOnce(&mut Encoder)>(self, symbol: &[u8], value: F) -> Self {
+        let mut this = self.with_tags();
+        let encoder = this.encoder();
+        encoder.append_symbol(symbol);
+
+        let depth   = encoder.sizers.len();
+        let written = encoder.counters.last().map(|counter| counter.written);
+        value(encoder);
+
+        if encoder.sizers.len() != depth {
+            encoder.fail(Error::UnbalancedSize);
+        }
+        else if let Some(before) = written {
+            let written = encoder.counters.last().unwrap().written - before;
+            if written != 1 {
+                encoder.fail(Error::CountMismatch { expected: 1, written });
+            }
+        }
+
+        this.tag_count += 1;
+        this
+    }
+
+    // writes the header, the caller appends the payload.
+    pub(crate) fn finish(mut self, wire_type: WireType) -> &'enc mut Encoder {
+        let encoder = self.encoder.take().unwrap();
+        if self.flags & WIRE_FLAG_TAGS != 0 {
+            if let Err(error) = encoder.end_deferred(self.tag_count_offset, self.tag_count) {
+                encoder.fail(error);
+            }
+        }
+        encoder.buffer[self.header_offset] = wire_type as u8 | self.flags;
+        encoder
+    }
+
+
+    pub fn payload_null(self) {
+        self.finish(WireType::Null);
+    }
+
+    pub fn payload_bool(self, value: bool) {
+        if value {
+            self.finish(WireType::BoolTrue);
         }
         else {
-            dest.extend(&self.buffer);
+            self.finish(WireType::BoolFalse);
+        }
+    }
+
+    pub fn payload_nat8(self, value: u8) {
+        self.finish(WireType::Nat8).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_nat16(self, value: u16) {
+        self.finish(WireType::Nat16).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_nat32(self, value: u32) {
+        self.finish(WireType::Nat32).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_nat64(self, value: u64) {
+        self.finish(WireType::Nat64).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_int8(self, value: i8) {
+        self.finish(WireType::Int8).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_int16(self, value: i16) {
+        self.finish(WireType::Int16).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_int32(self, value: i32) {
+        self.finish(WireType::Int32).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_int64(self, value: i64) {
+        self.finish(WireType::Int64).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_unsigned(self, value: u128) {
+        if let Ok(value) = u8::try_from(value) {
+            self.payload_nat8(value);
+        }
+        else if let Ok(value) = u16::try_from(value) {
+            self.payload_nat16(value);
+        }
+        else if let Ok(value) = u32::try_from(value) {
+            self.payload_nat32(value);
+        }
+        else if let Ok(value) = u64::try_from(value) {
+            self.payload_nat64(value);
+        }
+        else {
+            // note: little endian magnitude, without trailing zero bytes.
+            let length = 16 - value.leading_zeros() as usize / 8;
+            self.finish(WireType::Nat).append_size_prefixed(&value.to_le_bytes()[..length]);
+        }
+    }
+
+    pub fn payload_signed(self, value: i128) {
+        if let Ok(value) = i8::try_from(value) {
+            self.payload_int8(value);
+        }
+        else if let Ok(value) = i16::try_from(value) {
+            self.payload_int16(value);
+        }
+        else if let Ok(value) = i32::try_from(value) {
+            self.payload_int32(value);
+        }
+        else if let Ok(value) = i64::try_from(value) {
+            self.payload_int64(value);
+        }
+        else {
+            // note: little endian two's complement, without redundant sign
+            // extension bytes (the top bit of the last byte is the sign).
+            let redundant = if value < 0 { value.leading_ones() } else { value.leading_zeros() };
+            let length = 16 - (redundant as usize - 1) / 8;
+            self.finish(WireType::Int).append_size_prefixed(&value.to_le_bytes()[..length]);
+        }
+    }
+
+    // little endian magnitude of any length. redundant high bytes are
+    // dropped.
+    pub fn payload_nat(self, value: &[u8]) {
+        let length = value.iter().rposition(|byte| *byte != 0).map_or(0, |last| last + 1);
+        self.finish(WireType::Nat).append_size_prefixed(&value[..length]);
+    }
+
+    // little endian two's complement of any length. redundant sign
+    // extension bytes are dropped.
+    pub fn payload_int(self, value: &[u8]) {
+        let mut length = value.len();
+        while length > 0 {
+            let last = value[length - 1];
+            let sign = length >= 2 && value[length - 2] & 0x80 != 0;
+            if (last == 0x00 && !sign) || (last == 0xff && sign) {
+                length -= 1;
+            }
+            else {
+                break;
+            }
+        }
+        self.finish(WireType::Int).append_size_prefixed(&value[..length]);
+    }
+
+    pub fn payload_f32(self, value: f32) {
+        self.finish(WireType::Float32).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_f64(self, value: f64) {
+        self.finish(WireType::Float64).append(&value.to_le_bytes());
+    }
+
+    pub fn payload_bytes(self, value: &[u8]) {
+        self.finish(WireType::Bytes).append_size_prefixed(value);
+    }
+
+    pub fn payload_string(self, value: &str) {
+        self.finish(WireType::String).append_size_prefixed(value.as_bytes());
+    }
+
+    pub fn payload_decimal32(self, value: Decimal32) {
+        self.finish(WireType::Decimal32).append(&value.to_bits().to_le_bytes());
+    }
+
+    pub fn payload_decimal64(self, value: Decimal64) {
+        self.finish(WireType::Decimal64).append(&value.to_bits().to_le_bytes());
+    }
+
+    pub fn payload_symbol(self, value: &[u8]) {
+        self.finish(WireType::Symbol).append_symbol(value);
+    }
+
+    // note: the caller must write exactly `length` values, then `end_list`.
+    pub fn begin_list(self, length: usize) {
+        self.finish(WireType::List).begin_counted(Some(length));
+    }
+
+    pub fn begin_list_deferred(self) -> DeferredLength {
+        DeferredLength { offset: self.finish(WireType::List).begin_deferred() }
+    }
+
+    // `values` must write exactly `length` values. errors are reported by
+    // `build`.
+    pub fn payload_list<F: FnOnce(&mut Encoder)>(self, length: usize, values: F) {
+        let encoder = self.finish(WireType::List);
+        encoder.begin_counted(Some(length));
+        values(encoder);
+        if let Err(error) = encoder.end_counted() {
+            encoder.fail(error);
         }
-        Ok(())
     }
 }
 
-impl Default for Encoder {
-    fn default() -> Encoder {
-        Encoder::new(8, true)
+impl<'enc> Drop for ValueBuilder<'enc> {
+    fn drop(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            encoder.fail(Error::MissingPayload);
+        }
     }
 }
 
+// returned by `begin_list_deferred`, to be passed to `end_list_deferred`.
+pub struct DeferredLength {
+    offset: usize,
+}
+
 struct Sizer {
     offset: usize,
     size:   usize,
 }
 
+struct Counter {
+    expected: Option<usize>,
+    written:  usize,
+    depth:    usize,
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{self, OwnedValue, OwnedPayload};
+
+    fn string(length: usize) -> OwnedValue {
+        OwnedValue::new(OwnedPayload::String("x".repeat(length)))
+    }
+
+    fn list(values: Vec<OwnedValue>) -> OwnedValue {
+        OwnedValue::new(OwnedPayload::List(values))
+    }
+
+    // every size width, compressed and uncompressed.
+    fn options() -> Vec<EncoderOptions> {
+        let mut result = vec![];
+        for size_max_bytes in [1, 2, 4, 8] {
+            for compress_sizes in [true, false] {
+                result.push(EncoderOptions::new().size_max_bytes(size_max_bytes).compress_sizes(compress_sizes).checked(true));
+            }
+        }
+        result
+    }
+
+    // builds with every option, checks that the result decodes to `value`,
+    // and that compression doesn't depend on the placeholder width.
+    fn round_trip<F: Fn(&mut Encoder)>(encode: F, value: &OwnedValue) {
+        let mut compressed: Option<Vec<u8>> = None;
+        for options in options() {
+            let compress_sizes = options.compress_sizes;
+            let mut encoder = Encoder::new(options).unwrap();
+            encode(&mut encoder);
+            let bytes = encoder.build().unwrap();
+
+            crate::validate(&bytes).unwrap();
+            assert_eq!(&value::decode_owned(&bytes).unwrap(), value);
+
+            if compress_sizes {
+                match &compressed {
+                    Some(compressed) => assert_eq!(compressed, &bytes),
+                    None             => compressed = Some(bytes),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn widened_blocks() {
+        // the list payload is the count, the string's header and size, and
+        // its bytes. 63 and 16383 are the largest sizes with 1 and 2 bytes.
+        for (length, size) in [(59, 62), (61, 64), (16377, 16381), (16380, 16384), (70000, 70006)] {
+            let value = list(vec![string(length)]);
+            round_trip(|encoder| value.encode(encoder), &value);
+
+            for (size_max_bytes, max) in [(1, 63), (2, 16383)] {
+                let mut encoder = Encoder::new(EncoderOptions::new().size_max_bytes(size_max_bytes)).unwrap();
+                value.encode(&mut encoder);
+                assert_eq!(encoder.widened.iter().map(|(_, size)| *size).collect::<Vec<_>>(),
+                           if size > max { vec![size] } else { vec![] });
+            }
+        }
+    }
+
+    #[test]
+    fn nested_widened_blocks() {
+        let value = list(vec![
+            string(10),
+            list(vec![string(64), list(vec![string(16384)]), string(3)]),
+            list(vec![]),
+            list((0..100).map(|_| string(200)).collect()),
+        ]);
+        round_trip(|encoder| value.encode(encoder), &value);
+    }
+
+    #[test]
+    fn widened_tags() {
+        let tags = (0..70).map(|index| (format!("tag{}", index).into_bytes(), string(index * 4))).collect();
+        let value = OwnedValue {
+            kind:    Some(b"kind".to_vec()),
+            tags:    Some(tags),
+            payload: OwnedPayload::List(vec![string(20000)]),
+        };
+        round_trip(|encoder| value.encode(encoder), &value);
+
+        let empty = OwnedValue { kind: None, tags: Some(vec![]), payload: OwnedPayload::Null };
+        round_trip(|encoder| { encoder.value().with_tags().payload_null(); }, &empty);
+    }
+
+    // uncompressed placeholders keep their width. their size code must say
+    // so, the padding would be read as the next value otherwise.
+    #[test]
+    fn uncompressed_sizes() {
+        let list_header   = WireType::List as u8;
+        let string_header = WireType::String as u8;
+        for (size_max_bytes, size) in [(1, vec![5 << 2]), (2, vec![5 << 2 | 1, 0]), (4, vec![5 << 2 | 2, 0, 0, 0]), (8, vec![5 << 2 | 3, 0, 0, 0, 0, 0, 0, 0])] {
+            let mut encoder = Encoder::new(EncoderOptions::new().size_max_bytes(size_max_bytes).compress_sizes(false)).unwrap();
+            encoder.value().payload_list(1, |e| e.write_string("xx"));
+            let bytes = encoder.build().unwrap();
+
+            let mut expected = vec![list_header];
+            expected.extend(size);
+            expected.extend([1 << 2, string_header, 2 << 2, b'x', b'x']);
+            assert_eq!(bytes, expected);
+            assert_eq!(value::decode_owned(&bytes).unwrap(), list(vec![string(2)]));
+        }
+
+        // widened sizes are written with their minimal width.
+        let mut encoder = Encoder::new(EncoderOptions::new().size_max_bytes(1).compress_sizes(false)).unwrap();
+        encoder.value().payload_list(1, |e| e.write_string(&"x".repeat(100)));
+        let bytes = encoder.build().unwrap();
+        let size = (104u16 << 2 | 1).to_le_bytes();
+        assert_eq!(bytes[..5], [list_header, size[0], size[1], 1 << 2, string_header]);
+    }
+
+    #[test]
+    fn deferred_lists() {
+        for length in [0, 1, 63, 64, 5000] {
+            let value = list((0..length).map(|_| string(3)).collect());
+            round_trip(|encoder| {
+                let deferred = encoder.begin_list_deferred();
+                for _ in 0..length {
+                    encoder.write_string("xxx");
+                }
+                encoder.end_list_deferred(deferred, length).unwrap();
+            }, &value);
+        }
+
+        let value = list(vec![list(vec![string(100); 100]), string(1)]);
+        round_trip(|encoder| {
+            let outer = encoder.begin_list_deferred();
+            let inner = encoder.begin_list_deferred();
+            for _ in 0..100 {
+                encoder.write_string(&"x".repeat(100));
+            }
+            encoder.end_list_deferred(inner, 100).unwrap();
+            encoder.write_string("x");
+            encoder.end_list_deferred(outer, 2).unwrap();
+        }, &value);
+    }
+
+    #[test]
+    fn invalid_size_max_bytes() {
+        for size_max_bytes in [0, 3, 5, 16] {
+            let result = Encoder::new(EncoderOptions::new().size_max_bytes(size_max_bytes));
+            assert!(matches!(result, Err(Error::InvalidSizeMaxBytes(value)) if value == size_max_bytes));
+        }
+        for size_max_bytes in [1, 2, 4, 8] {
+            Encoder::new(EncoderOptions::new().size_max_bytes(size_max_bytes)).unwrap();
+        }
+    }
+
+    #[test]
+    fn reuse() {
+        let options = EncoderOptions::new().size_max_bytes(1).intern_symbols(true).checked(true);
+        let write = |e: &mut Encoder| {
+            e.value().kind(b"kind").payload_list(2, |e| {
+                e.write_symbol(b"kind");
+                e.write_string(&"x".repeat(100));
+            });
+        };
+        let mut fresh = Encoder::new(options.clone()).unwrap();
+        write(&mut fresh);
+        let expected = fresh.build().unwrap();
+
+        // a failed document, with interned symbols, a widened size, an open
+        // deferred list and an error.
+        let mut encoder = Encoder::new(options).unwrap();
+        encoder.write_symbol(b"other");
+        encoder.write_symbol(b"other");
+        encoder.write_string(&"x".repeat(100));
+        let _ = encoder.begin_list_deferred();
+        encoder.begin_list(3);
+        let _ = encoder.value();
+        let capacity = encoder.buffer.capacity();
+        let mut dest = vec![];
+        assert_eq!(encoder.build_into(&mut dest), Err(Error::MissingPayload));
+        assert!(dest.is_empty());
+
+        assert_eq!(encoder.size(), Ok(0));
+        assert!(encoder.buffer.capacity() >= capacity);
+        write(&mut encoder);
+        encoder.build_into(&mut dest).unwrap();
+        assert_eq!(dest, expected);
+
+        // `build_into` appends, and resets after a success too.
+        write(&mut encoder);
+        encoder.build_into(&mut dest).unwrap();
+        assert_eq!(dest, [&expected[..], &expected[..]].concat());
+
+        encoder.write_symbol(b"other");
+        encoder.reset();
+        write(&mut encoder);
+        assert_eq!(encoder.build().unwrap(), expected);
+    }
+
+    #[test]
+    fn builder_misuse() {
+        let mut encoder = Encoder::default();
+        encoder.value().kind(b"a").kind(b"b").payload_null();
+        assert_eq!(encoder.build(), Err(Error::MisplacedKind));
+
+        let mut encoder = Encoder::default();
+        encoder.value().tag(b"a", |e| e.write_null()).kind(b"b").payload_null();
+        assert_eq!(encoder.build(), Err(Error::MisplacedKind));
+
+        let mut encoder = Encoder::default();
+        let _ = encoder.value().kind(b"a");
+        assert_eq!(encoder.build(), Err(Error::MissingPayload));
+
+        // the first error is reported.
+        let mut encoder = Encoder::default();
+        encoder.value().payload_list(1, |e| {
+            let _ = e.value();
+        });
+        encoder.value().kind(b"a").kind(b"b").payload_null();
+        assert_eq!(encoder.build(), Err(Error::MissingPayload));
+    }
+
+    fn checked() -> Encoder {
+        Encoder::new(EncoderOptions::new().checked(true)).unwrap()
+    }
+
+    #[test]
+    fn checked_list_counts() {
+        for written in [0, 1, 3] {
+            let mut encoder = checked();
+            encoder.begin_list(2);
+            for _ in 0..written {
+                encoder.write_null();
+            }
+            assert_eq!(encoder.end_list(), Err(Error::CountMismatch { expected: 2, written }));
+        }
+
+        let mut encoder = checked();
+        encoder.begin_list(2);
+        encoder.write_null();
+        encoder.write_null();
+        encoder.end_list().unwrap();
+        encoder.build().unwrap();
+
+        let mut encoder = checked();
+        encoder.value().payload_list(2, |e| e.write_null());
+        assert_eq!(encoder.build(), Err(Error::CountMismatch { expected: 2, written: 1 }));
+    }
+
+    #[test]
+    fn checked_tag_counts() {
+        let mut encoder = checked();
+        encoder.append_byte(WireType::Null as u8 | WIRE_FLAG_TAGS);
+        encoder.begin_tags(2);
+        encoder.append_symbol(b"a");
+        encoder.write_null();
+        assert_eq!(encoder.end_tags(), Err(Error::CountMismatch { expected: 2, written: 1 }));
+
+        let mut encoder = checked();
+        encoder.value().tag(b"a", |e| { e.write_null(); e.write_null(); }).payload_null();
+        assert_eq!(encoder.build(), Err(Error::CountMismatch { expected: 1, written: 2 }));
+    }
+
+    #[test]
+    fn unbalanced_sizes() {
+        let mut encoder = checked();
+        assert_eq!(encoder.end_size(), Err(Error::UnbalancedSize));
+        assert_eq!(encoder.end_list(), Err(Error::UnbalancedSize));
+
+        // `end_list` doesn't end a raw size block, and leaves it open.
+        let mut encoder = checked();
+        encoder.begin_list(1);
+        encoder.begin_size();
+        encoder.write_null();
+        assert_eq!(encoder.end_list(), Err(Error::UnbalancedSize));
+        encoder.end_size().unwrap();
+        encoder.end_list().unwrap();
+        encoder.build().unwrap();
+
+        // open blocks are reported instead of panicking.
+        let mut encoder = checked();
+        encoder.begin_list(1);
+        assert_eq!(encoder.size(), Err(Error::UnbalancedSize));
+        assert_eq!(encoder.build(), Err(Error::UnbalancedSize));
+
+        let mut encoder = Encoder::default();
+        encoder.begin_size();
+        assert_eq!(encoder.size(), Err(Error::UnbalancedSize));
+        assert_eq!(encoder.build(), Err(Error::UnbalancedSize));
+    }
+
+
+    // the document `write` builds, which must be a single value.
+    fn single<F: FnOnce(&mut Encoder)>(write: F) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        write(&mut encoder);
+        encoder.build().unwrap()
+    }
+
+    fn fixed(wire_type: WireType, bytes: &[u8]) -> Vec<u8> {
+        let mut result = vec![wire_type as u8];
+        result.extend(bytes);
+        result
+    }
+
+    fn prefixed(wire_type: WireType, bytes: &[u8]) -> Vec<u8> {
+        let mut result = vec![wire_type as u8, (bytes.len() << 2) as u8];
+        result.extend(bytes);
+        result
+    }
+
+    #[test]
+    fn minimal_integer_widths() {
+        assert_eq!(single(|e| e.write_unsigned(255)),     fixed(WireType::Nat8,  &[0xff]));
+        assert_eq!(single(|e| e.write_unsigned(256)),     fixed(WireType::Nat16, &[0x00, 0x01]));
+        assert_eq!(single(|e| e.write_unsigned(1 << 16)), fixed(WireType::Nat32, &[0x00, 0x00, 0x01, 0x00]));
+        assert_eq!(single(|e| e.write_unsigned(1 << 32)), fixed(WireType::Nat64, &(1u64 << 32).to_le_bytes()));
+        assert_eq!(single(|e| e.write_unsigned(1 << 64)), prefixed(WireType::Nat, &[0, 0, 0, 0, 0, 0, 0, 0, 1]));
+        assert_eq!(single(|e| e.write_unsigned(u128::MAX)), prefixed(WireType::Nat, &[0xff; 16]));
+
+        assert_eq!(single(|e| e.write_signed(-1)),   fixed(WireType::Int8,  &[0xff]));
+        assert_eq!(single(|e| e.write_signed(-128)), fixed(WireType::Int8,  &[0x80]));
+        assert_eq!(single(|e| e.write_signed(-129)), fixed(WireType::Int16, &[0x7f, 0xff]));
+        assert_eq!(single(|e| e.write_signed(128)),  fixed(WireType::Int16, &[0x80, 0x00]));
+        assert_eq!(single(|e| e.write_signed(i64::MIN as i128 - 1)),
+                   prefixed(WireType::Int, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 0xff]));
+        assert_eq!(single(|e| e.write_signed(1 << 63)),
+                   prefixed(WireType::Int, &[0, 0, 0, 0, 0, 0, 0, 0x80, 0x00]));
+        assert_eq!(single(|e| e.write_signed(i128::MIN)), prefixed(WireType::Int, &(i128::MIN).to_le_bytes()));
+    }
+
+    #[test]
+    fn canonical_raw_integers() {
+        assert_eq!(single(|e| e.write_nat(&[5, 0, 0])), prefixed(WireType::Nat, &[5]));
+        assert_eq!(single(|e| e.write_nat(&[0, 0])),    prefixed(WireType::Nat, &[]));
+        assert_eq!(single(|e| e.write_nat(&[0, 1])),    prefixed(WireType::Nat, &[0, 1]));
+
+        assert_eq!(single(|e| e.write_int(&[0xff, 0xff])),       prefixed(WireType::Int, &[0xff]));
+        assert_eq!(single(|e| e.write_int(&[0x80, 0xff, 0xff])), prefixed(WireType::Int, &[0x80]));
+        assert_eq!(single(|e| e.write_int(&[0x7f, 0xff])),       prefixed(WireType::Int, &[0x7f, 0xff]));
+        assert_eq!(single(|e| e.write_int(&[0x80, 0x00])),       prefixed(WireType::Int, &[0x80, 0x00]));
+        assert_eq!(single(|e| e.write_int(&[0x7f, 0x00, 0x00])), prefixed(WireType::Int, &[0x7f]));
+        assert_eq!(single(|e| e.write_int(&[0x00, 0x00])),       prefixed(WireType::Int, &[]));
+    }
+}
@@ -0,0 +1,774 @@
+use std::cell::Cell;
+use std::borrow::Cow;
+use serde::de::{self, DeserializeSeed, Visitor, MapAccess, SeqAccess};
+use serde::ser::{self, Serialize, Serializer, SerializeMap, SerializeSeq};
+use serde_json::{Number, Map};
+use slice_reader::Reader;
+use crate::encoder::{self, Encoder};
+use crate::decoder::{self, DecodeError, Stage, Reason, Payload, Value, DecodeOptions};
+use crate::decimal::Decimal64;
+use crate::codec::{self, Encode, Decode, Tags, List};
+
+
+// json values map to udoc like this:
+//  - objects are `Null` with tags, in order.
+//  - integers use the narrowest `Nat*` or `Int*` wire type, larger ones
+//    (with `json-arbitrary-precision`) are `Nat` or `Int`.
+//  - other numbers are `Float64`, if that round trips exactly. otherwise
+//    `Decimal64`, if it formats back to the same text. otherwise the text,
+//    as a string with the kind `NUMBER_KIND`.
+//
+// decoding accepts any numeric wire type and symbols as strings. kinds other
+// than `NUMBER_KIND` are ignored.
+//
+// without the `json-arbitrary-precision` feature, which enables serde_json's
+// `arbitrary_precision`, `serde_json::Number` holds at most an `f64`: json
+// integers beyond 64 bits and decimals that don't fit an `f64` are rounded
+// when parsed, and `Nat`, `Int`, `Decimal*` and `NUMBER_KIND` values are
+// rounded when decoded, or fail with `OutOfRange`.
+//
+// besides the `serde_json::Value` impls, json text can be converted directly,
+// without building a tree: `from_slice`, `from_reader` and `transcode` read
+// json text, `to_writer` writes it.
+//
+// udoc nested deeper than the `DecodeOptions` allow fails with
+// `Reason::DepthLimit`. the default limit matches the nesting serde_json
+// parses.
+
+pub const NUMBER_KIND: &[u8] = b"number";
+
+
+#[derive(Debug)]
+pub enum Error {
+    Json    (serde_json::Error),
+    Encoder (encoder::Error),
+    Decode  (DecodeError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Json    (error) => write!(f, "{}", error),
+            Error::Encoder (error) => write!(f, "encoder error: {}", error),
+            Error::Decode  (error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Error {
+        Error::Json(error)
+    }
+}
+
+impl From<encoder::Error> for Error {
+    fn from(error: encoder::Error) -> Error {
+        Error::Encoder(error)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Error {
+        Error::Decode(error)
+    }
+}
+
+
+impl Encode for Number {
+    fn encode(&self, encoder: &mut Encoder) {
+        // note: with `arbitrary_precision`, the checks keep the exact text.
+        if let Some(value) = self.as_u64().filter(|value| Number::from(*value) == *self) {
+            return encoder.write_unsigned(value as u128);
+        }
+        if let Some(value) = self.as_i64().filter(|value| Number::from(*value) == *self) {
+            return encoder.write_signed(value as i128);
+        }
+
+        // note: without `arbitrary_precision`, this always returns.
+        if let Some(value) = self.as_f64() {
+            if Number::from_f64(value).as_ref() == Some(self) {
+                return encoder.write_f64(value);
+            }
+        }
+
+        // note: `-0` is left to `Decimal64`, to keep the sign.
+        let text = self.to_string();
+        if let Some(digits) = text.strip_prefix('-').filter(|digits| is_integer(digits) && *digits != "0") {
+            let mut magnitude = decimal_to_nat(digits);
+            magnitude.push(0);
+            return encoder.write_int(&negate(magnitude));
+        }
+        if is_integer(&text) {
+            return encoder.write_nat(&decimal_to_nat(&text));
+        }
+        if let Ok(value) = text.parse::<Decimal64>() {
+            if value.to_string() == text {
+                return encoder.write_decimal64(value);
+            }
+        }
+        encoder.value().kind(NUMBER_KIND).payload_string(&text);
+    }
+}
+
+impl<'de> Decode<'de> for Number {
+    fn decode(value: &Value<'de>) -> Result<Number, DecodeError> {
+        if value.header.has_kind && value.kind == NUMBER_KIND {
+            let text: &str = codec::wire::string(value)?;
+            return text.parse().map_err(|_| DecodeError::unexpected_type("number", value));
+        }
+
+        let text = match value.payload {
+            Payload::Nat8 (_) | Payload::Nat16 (_) | Payload::Nat32 (_) | Payload::Nat64 (_) |
+            Payload::Int8 (_) | Payload::Int16 (_) | Payload::Int32 (_) | Payload::Int64 (_) => {
+                return match value.as_u64() {
+                    Ok(unsigned) => Ok(unsigned.into()),
+                    Err(_)       => Ok(value.as_i64().map_err(|_| DecodeError::out_of_range(value))?.into()),
+                };
+            },
+
+            Payload::Float32 (_) | Payload::Float64 (_) => {
+                let float = value.as_f64().map_err(|_| DecodeError::out_of_range(value))?;
+                return Number::from_f64(float).ok_or_else(|| DecodeError::out_of_range(value));
+            },
+
+            Payload::Nat (bytes) => nat_to_decimal(bytes),
+            Payload::Int (bytes) => {
+                if bytes.last().is_some_and(|byte| byte & 0x80 != 0) {
+                    format!("-{}", nat_to_decimal(&negate(bytes.to_vec())))
+                }
+                else {
+                    nat_to_decimal(bytes)
+                }
+            },
+
+            Payload::Decimal32 (decimal) if decimal.is_finite() => decimal.to_string(),
+            Payload::Decimal64 (decimal) if decimal.is_finite() => decimal.to_string(),
+            Payload::Decimal32 (_) | Payload::Decimal64 (_) => {
+                return Err(DecodeError::out_of_range(value));
+            },
+
+            _ => return Err(DecodeError::unexpected_type("number", value)),
+        };
+
+        // note: without `arbitrary_precision`, large integers become floats.
+        text.parse().map_err(|_| DecodeError::out_of_range(value))
+    }
+}
+
+
+impl Encode for serde_json::Value {
+    fn encode(&self, encoder: &mut Encoder) {
+        use serde_json::Value::*;
+        match self {
+            Null             => encoder.write_null(),
+            Bool   (value)   => encoder.write_bool(*value),
+            Number (value)   => value.encode(encoder),
+            String (value)   => encoder.write_string(value),
+
+            Array (values) => {
+                encoder.value().payload_list(values.len(), |encoder| {
+                    for value in values {
+                        value.encode(encoder);
+                    }
+                });
+            },
+
+            Object (values) => {
+                let mut builder = encoder.value().with_tags();
+                for (key, value) in values {
+                    builder = builder.tag(key.as_bytes(), |encoder| value.encode(encoder));
+                }
+                builder.payload_null();
+            },
+        }
+    }
+}
+
+impl<'de> Decode<'de> for serde_json::Value {
+    fn decode(value: &Value<'de>) -> Result<serde_json::Value, DecodeError> {
+        use serde_json::Value as Json;
+
+        if value.header.has_kind && value.kind == NUMBER_KIND {
+            return Ok(Json::Number(Number::decode(value)?));
+        }
+
+        Ok(match value.payload {
+            Payload::Null if value.header.has_tags => {
+                let mut result = Map::new();
+                let mut tags = Tags::new(value)?;
+                for (symbol, tag) in &mut tags {
+                    let key   = utf8_symbol(symbol).ok_or_else(|| DecodeError::invalid_tag(value, symbol))?;
+                    let value = Json::decode(&tag).map_err(|e| e.in_tag(symbol))?;
+                    result.insert(key.into(), value);
+                }
+                tags.finish()?;
+                Json::Object(result)
+            },
+
+            Payload::Null            => Json::Null,
+            Payload::Bool (value)    => Json::Bool(value),
+            Payload::String (_)      => Json::String(codec::wire::string(value)?),
+            Payload::Symbol (symbol) => Json::String(utf8_symbol(symbol).ok_or_else(|| DecodeError::invalid_symbol(value))?.into()),
+
+            Payload::List (_) => {
+                let mut list = List::new(value)?;
+                let mut result = Vec::with_capacity(list.len());
+                for index in 0..list.len() {
+                    result.push(Json::decode(&list.element()?).map_err(|e| e.in_index(index))?);
+                }
+                list.finish()?;
+                Json::Array(result)
+            },
+
+            Payload::Bytes (_) => return Err(DecodeError::unexpected_type("json value", value)),
+
+            _ => Json::Number(Number::decode(value)?),
+        })
+    }
+}
+
+
+// json text to udoc.
+
+pub fn from_slice(json: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = Encoder::default();
+    let mut json = serde_json::Deserializer::from_slice(json);
+    transcode(&mut encoder, &mut json)?;
+    json.end()?;
+    Ok(encoder.build()?)
+}
+
+pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Vec<u8>, Error> {
+    let mut encoder = Encoder::default();
+    let mut json = serde_json::Deserializer::from_reader(reader);
+    transcode(&mut encoder, &mut json)?;
+    json.end()?;
+    Ok(encoder.build()?)
+}
+
+// writes the next json value of `json` to `encoder`.
+// for streams of values, call this once per value.
+// on error, the encoder holds a partial document and should be `reset`.
+pub fn transcode<'de, R: serde_json::de::Read<'de>>(encoder: &mut Encoder, json: &mut serde_json::Deserializer<R>) -> Result<(), serde_json::Error> {
+    Transcode { encoder }.deserialize(json)
+}
+
+
+// serde_json's `arbitrary_precision` passes numbers as a map with this key.
+const ARBITRARY_PRECISION_TOKEN: &str = "$serde_json::private::Number";
+
+struct Transcode<'enc> {
+    encoder: &'enc mut Encoder,
+}
+
+impl<'de, 'enc> DeserializeSeed<'de> for Transcode<'enc> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'enc> Visitor<'de> for Transcode<'enc> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a json value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        self.encoder.write_null();
+        Ok(())
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<(), E> {
+        self.encoder.write_bool(value);
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<(), E> {
+        self.encoder.write_unsigned(value as u128);
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<(), E> {
+        self.encoder.write_signed(value as i128);
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<(), E> {
+        self.encoder.write_f64(value);
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<(), E> {
+        self.encoder.write_string(value);
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let deferred = self.encoder.begin_list_deferred();
+        let mut length = 0;
+        while seq.next_element_seed(Transcode { encoder: self.encoder })?.is_some() {
+            length += 1;
+        }
+        self.encoder.end_list_deferred(deferred, length).map_err(de::Error::custom)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut key = map.next_key_seed(Key)?;
+
+        if key.as_deref() == Some(ARBITRARY_PRECISION_TOKEN) {
+            let number: String = map.next_value()?;
+            number.parse::<Number>().map_err(de::Error::custom)?.encode(self.encoder);
+            return Ok(());
+        }
+
+        let mut builder = self.encoder.value().with_tags();
+        let mut error   = None;
+        while let Some(symbol) = key {
+            builder = builder.tag(symbol.as_bytes(), |encoder| {
+                error = map.next_value_seed(Transcode { encoder }).err();
+            });
+            if let Some(error) = error {
+                builder.payload_null();
+                return Err(error);
+            }
+            key = map.next_key_seed(Key)?;
+        }
+        builder.payload_null();
+        Ok(())
+    }
+}
+
+struct Key;
+
+impl<'de> DeserializeSeed<'de> for Key {
+    type Value = Cow<'de, str>;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Cow<'de, str>, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Key {
+    type Value = Cow<'de, str>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a string")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, value: &'de str) -> Result<Cow<'de, str>, E> {
+        Ok(Cow::Borrowed(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Cow<'de, str>, E> {
+        Ok(Cow::Owned(value.into()))
+    }
+}
+
+
+// udoc to json text.
+
+pub fn to_writer<W: std::io::Write>(writer: W, udoc: &[u8]) -> Result<(), Error> {
+    to_writer_with_options(writer, udoc, &DecodeOptions::new())
+}
+
+pub fn to_writer_with_options<W: std::io::Write>(writer: W, udoc: &[u8], options: &DecodeOptions) -> Result<(), Error> {
+    let mut reader = Reader::new(udoc);
+    let value = decoder::decode_document_with_options(&mut reader, options)?;
+    if reader.has_some() {
+        let error = DecodeError::new(Stage::Trailing, Reason::TrailingBytes, &reader);
+        return Err(error.into());
+    }
+
+    let error = Cell::new(None);
+    serde_json::to_writer(writer, &Json { value, error: &error })
+        .map_err(|e| match error.take() {
+            Some(error) => Error::Decode(error),
+            None        => Error::Json(e),
+        })
+}
+
+
+// serde can't carry `DecodeError`, so it is stored in `error`, and the
+// serializer gets a placeholder.
+struct Json<'de, 'a> {
+    value: Value<'de>,
+    error: &'a Cell<Option<DecodeError>>,
+}
+
+impl<'de, 'a> Json<'de, 'a> {
+    fn child(&self, value: Value<'de>) -> Json<'de, 'a> {
+        Json { value, error: self.error }
+    }
+
+    fn fail<E: ser::Error>(&self, error: DecodeError) -> E {
+        self.error.set(Some(error));
+        E::custom("invalid udoc")
+    }
+
+    fn annotate<E, F: FnOnce(DecodeError) -> DecodeError>(&self, error: E, f: F) -> E {
+        if let Some(inner) = self.error.take() {
+            self.error.set(Some(f(inner)));
+        }
+        error
+    }
+}
+
+impl<'de, 'a> Serialize for Json<'de, 'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = self.value;
+        if value.header.has_kind && value.kind == NUMBER_KIND {
+            return Number::decode(&value).map_err(|e| self.fail(e))?.serialize(serializer);
+        }
+
+        match value.payload {
+            Payload::Null if value.header.has_tags => {
+                let mut tags = Tags::new(&value).map_err(|e| self.fail(e))?;
+                let mut map = serializer.serialize_map(None)?;
+                for (symbol, value) in &mut tags {
+                    let key = utf8_symbol(symbol).ok_or_else(|| self.fail(DecodeError::invalid_tag(&self.value, symbol)))?;
+                    map.serialize_entry(key, &self.child(value))
+                        .map_err(|e| self.annotate(e, |error| error.in_tag(symbol)))?;
+                }
+                tags.finish().map_err(|e| self.fail(e))?;
+                map.end()
+            },
+
+            Payload::Null            => serializer.serialize_unit(),
+            Payload::Bool (value)    => serializer.serialize_bool(value),
+            Payload::Symbol (symbol) => serializer.serialize_str(utf8_symbol(symbol).ok_or_else(|| self.fail(DecodeError::invalid_symbol(&value)))?),
+
+            Payload::String (_) => {
+                let string: &str = codec::wire::string(&value).map_err(|e| self.fail(e))?;
+                serializer.serialize_str(string)
+            },
+
+            Payload::List (_) => {
+                let mut list = List::new(&value).map_err(|e| self.fail(e))?;
+                let mut seq = serializer.serialize_seq(Some(list.len()))?;
+                for index in 0..list.len() {
+                    let element = list.element().map_err(|e| self.fail(e))?;
+                    seq.serialize_element(&self.child(element))
+                        .map_err(|e| self.annotate(e, |error| error.in_index(index)))?;
+                }
+                list.finish().map_err(|e| self.fail(e))?;
+                seq.end()
+            },
+
+            Payload::Bytes (_) => Err(self.fail(DecodeError::unexpected_type("json value", &value))),
+
+            _ => Number::decode(&value).map_err(|e| self.fail(e))?.serialize(serializer),
+        }
+    }
+}
+
+
+fn utf8_symbol(symbol: &[u8]) -> Option<&str> {
+    std::str::from_utf8(symbol).ok()
+}
+
+fn is_integer(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|c| c.is_ascii_digit())
+}
+
+// little endian magnitude of a decimal digit string.
+fn decimal_to_nat(digits: &str) -> Vec<u8> {
+    let mut result = vec![];
+    for digit in digits.bytes() {
+        let mut carry = (digit - b'0') as u32;
+        for byte in result.iter_mut() {
+            let value = *byte as u32 * 10 + carry;
+            *byte = value as u8;
+            carry = value >> 8;
+        }
+        if carry != 0 {
+            result.push(carry as u8);
+        }
+    }
+    result
+}
+
+fn nat_to_decimal(bytes: &[u8]) -> String {
+    let mut bytes = bytes.to_vec();
+    let mut digits = vec![];
+    while bytes.iter().any(|byte| *byte != 0) {
+        let mut remainder = 0u32;
+        for byte in bytes.iter_mut().rev() {
+            let value = remainder << 8 | *byte as u32;
+            *byte = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+// two's complement negation, modulo the length of `bytes`.
+fn negate(mut bytes: Vec<u8>) -> Vec<u8> {
+    let mut carry = true;
+    for byte in bytes.iter_mut() {
+        let (value, overflow) = (!*byte).overflowing_add(carry as u8);
+        *byte = value;
+        carry = overflow;
+    }
+    bytes
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire_type::WireType;
+    use crate::decoder::{Path, PathSegment, Invalid};
+
+    const ARBITRARY_PRECISION: bool = cfg!(feature = "json-arbitrary-precision");
+
+    fn build<F: FnOnce(&mut Encoder)>(f: F) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        f(&mut encoder);
+        encoder.build().unwrap()
+    }
+
+    // encodes `text` as a `Number`, checks `from_slice` agrees, and returns the udoc.
+    fn number(text: &str) -> Vec<u8> {
+        let number: Number = serde_json::from_str(text).unwrap();
+        let bytes = codec::encode(&number).unwrap();
+        assert_eq!(from_slice(text.as_bytes()).unwrap(), bytes, "{}", text);
+        bytes
+    }
+
+    fn json(udoc: &[u8]) -> String {
+        let mut result = vec![];
+        to_writer(&mut result, udoc).unwrap();
+        String::from_utf8(result).unwrap()
+    }
+
+
+    #[test]
+    fn integer_limits() {
+        let max = number("18446744073709551615");
+        assert_eq!(max, build(|e| e.write_unsigned(u64::MAX as u128)));
+        assert_eq!(codec::decode::<Number>(&max).unwrap().as_u64(), Some(u64::MAX));
+        assert_eq!(json(&max), "18446744073709551615");
+
+        let min = number("-9223372036854775808");
+        assert_eq!(min, build(|e| e.write_signed(i64::MIN as i128)));
+        assert_eq!(codec::decode::<Number>(&min).unwrap().as_i64(), Some(i64::MIN));
+        assert_eq!(json(&min), "-9223372036854775808");
+    }
+
+    #[test]
+    fn large_integers() {
+        let two_64 = [0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let nat = number("18446744073709551616");
+        let int = number("-18446744073709551617");
+        if ARBITRARY_PRECISION {
+            assert_eq!(nat, build(|e| e.write_nat(&two_64)));
+            assert_eq!(int, build(|e| e.write_int(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe])));
+            assert_eq!(json(&nat), "18446744073709551616");
+            assert_eq!(json(&int), "-18446744073709551617");
+        }
+        else {
+            // rounded when parsed.
+            assert_eq!(nat, build(|e| e.write_f64(18446744073709551616.0)));
+            assert_eq!(int, build(|e| e.write_f64(-18446744073709551616.0)));
+        }
+
+        // `Nat` decodes exactly, or rounded to an `f64`.
+        let nat = build(|e| e.write_nat(&two_64));
+        let expected = if ARBITRARY_PRECISION { "18446744073709551616" } else { "1.8446744073709552e+19" };
+        assert_eq!(json(&nat), expected);
+    }
+
+    #[test]
+    fn decimals() {
+        // exact as an `f64`.
+        assert_eq!(number("0.1"), build(|e| e.write_f64(0.1)));
+        assert_eq!(json(&number("0.1")), "0.1");
+        assert_eq!(number("-1.5e300"), build(|e| e.write_f64(-1.5e300)));
+
+        // trailing zeros survive `Decimal64`, but not `f64`.
+        let trailing = number("1.10");
+        let decimal = build(|e| e.write_decimal64("1.10".parse().unwrap()));
+        if ARBITRARY_PRECISION {
+            assert_eq!(trailing, decimal);
+            assert_eq!(json(&trailing), "1.10");
+        }
+        else {
+            assert_eq!(trailing, build(|e| e.write_f64(1.1)));
+        }
+        assert_eq!(json(&decimal), if ARBITRARY_PRECISION { "1.10" } else { "1.1" });
+
+        // more digits than `Decimal64` holds.
+        let long = number("0.12345678901234567890");
+        if ARBITRARY_PRECISION {
+            assert_eq!(long, build(|e| { e.value().kind(NUMBER_KIND).payload_string("0.12345678901234567890"); }));
+            assert_eq!(json(&long), "0.12345678901234567890");
+        }
+        else {
+            assert_eq!(json(&long), "0.12345678901234568");
+        }
+    }
+
+    #[test]
+    fn number_kind() {
+        let udoc = build(|e| { e.value().kind(NUMBER_KIND).payload_string("1e+400"); });
+        if ARBITRARY_PRECISION {
+            let decoded = codec::decode::<Number>(&udoc).unwrap();
+            assert_eq!(decoded, "1e400".parse().unwrap());
+            assert_eq!(codec::encode(&decoded).unwrap(), udoc);
+            assert_eq!(number("1e400"), udoc);
+            assert_eq!(codec::decode::<serde_json::Value>(&udoc).unwrap(), serde_json::Value::Number(decoded));
+            assert_eq!(json(&udoc), "1e+400");
+        }
+        else {
+            // `1e+400` doesn't fit an `f64`.
+            let error = codec::decode::<Number>(&udoc).unwrap_err();
+            assert_eq!((error.reason, error.wire_type, error.offset),
+                (Reason::InvalidValue(Invalid::UnexpectedType { expected: "number" }), Some(WireType::String), 0));
+        }
+
+        // the kind is required, other strings stay strings.
+        let string = build(|e| e.write_string("1"));
+        assert_eq!(codec::decode::<serde_json::Value>(&string).unwrap(), serde_json::Value::String("1".into()));
+        assert_eq!(json(&string), "\"1\"");
+    }
+
+    #[test]
+    fn negative_zero() {
+        let zero = number("-0");
+        if ARBITRARY_PRECISION {
+            assert_eq!(zero, build(|e| e.write_decimal64("-0".parse().unwrap())));
+            assert_eq!(json(&zero), "-0");
+        }
+        else {
+            assert_eq!(zero, build(|e| e.write_f64(-0.0)));
+            assert_eq!(json(&zero), "-0.0");
+        }
+
+        assert_eq!(json(&build(|e| e.write_f64(-0.0))), "-0.0");
+        assert_eq!(json(&build(|e| e.write_decimal64("-0".parse().unwrap()))), if ARBITRARY_PRECISION { "-0" } else { "-0.0" });
+    }
+
+    // checks the direct conversions against the `serde_json::Value` impls.
+    // note: `serde_json::Map` sorts keys, `from_slice` keeps their order, so
+    // objects here have sorted keys.
+    fn round_trip(text: &str) {
+        let tree: serde_json::Value = serde_json::from_str(text).unwrap();
+        let udoc = codec::encode(&tree).unwrap();
+        assert_eq!(from_slice(text.as_bytes()).unwrap(), udoc, "{}", text);
+        assert_eq!(from_reader(text.as_bytes()).unwrap(), udoc, "{}", text);
+        assert_eq!(codec::decode::<serde_json::Value>(&udoc).unwrap(), tree);
+        assert_eq!(json(&udoc), tree.to_string());
+    }
+
+    #[test]
+    fn containers() {
+        round_trip("[]");
+        round_trip("{}");
+        round_trip(r#"[1, [true, null], {"a": [], "b": {"c": "x"}}, []]"#);
+        round_trip(r#"{"": "", "a": {"b": [{"c": [[]]}]}, "d": -1}"#);
+        round_trip(r#"[[0.5, -0, 1.10], {"m": [0.12345678901234567890], "n": 18446744073709551616}]"#);
+        if ARBITRARY_PRECISION {
+            round_trip(r#"{"a": [1e400, {"b": -1e-400}]}"#);
+        }
+
+        // deferred lengths, that don't fit the reserved size byte.
+        let long = format!("[{}]", vec!["[0]"; 300].join(","));
+        round_trip(&long);
+        let wide = format!("{{{}}}", (0..300).map(|i| format!(r#""{:03}": [{}]"#, i, i)).collect::<Vec<_>>().join(","));
+        round_trip(&wide);
+    }
+
+    #[test]
+    fn transcode_stream() {
+        let mut encoder = Encoder::default();
+        let mut json = serde_json::Deserializer::from_str(r#"1 [2, 3] {"a": 4}"#);
+        for text in ["1", "[2, 3]", r#"{"a": 4}"#] {
+            transcode(&mut encoder, &mut json).unwrap();
+            let mut udoc = vec![];
+            encoder.build_into(&mut udoc).unwrap();
+            assert_eq!(udoc, from_slice(text.as_bytes()).unwrap());
+        }
+        json.end().unwrap();
+
+        // an encoder can be reused after a failed value, once reset.
+        let mut json = serde_json::Deserializer::from_str(r#"[1, {"a": x}] [5]"#);
+        assert!(transcode(&mut encoder, &mut json).is_err());
+        encoder.reset();
+        let mut json = serde_json::Deserializer::from_str("[5]");
+        transcode(&mut encoder, &mut json).unwrap();
+        assert_eq!(encoder.build().unwrap(), from_slice(b"[5]").unwrap());
+    }
+
+    #[test]
+    fn json_errors() {
+        for text in ["", "[1,", r#"{"a": tru}"#, r#"{"a" 1}"#, "[1] 2", r#"{"a": [1, }"#] {
+            assert!(matches!(from_slice(text.as_bytes()), Err(Error::Json(_))), "{}", text);
+            assert!(matches!(from_reader(text.as_bytes()), Err(Error::Json(_))), "{}", text);
+        }
+    }
+
+    #[test]
+    fn udoc_errors() {
+        let mut udoc = from_slice(b"[1]").unwrap();
+        udoc.push(0);
+        match to_writer(vec![], &udoc) {
+            Err(Error::Decode(error)) => assert_eq!(error.reason, Reason::TrailingBytes),
+            _ => panic!("expected trailing bytes"),
+        }
+
+        // errors found while serializing carry the path, not serde_json's placeholder.
+        let bytes = build(|e| {
+            e.value().tag(b"a", |e| {
+                e.value().payload_list(2, |e| {
+                    e.write_null();
+                    e.write_bytes(b"x");
+                });
+            }).payload_null();
+        });
+        let expected = codec::decode::<serde_json::Value>(&bytes).unwrap_err();
+        assert_eq!((expected.reason, expected.wire_type),
+            (Reason::InvalidValue(Invalid::UnexpectedType { expected: "json value" }), Some(WireType::Bytes)));
+        assert_eq!(expected.header, Some(bytes[expected.offset]));
+        assert_eq!(expected.path, Path(vec![PathSegment::Tag(b"a".to_vec()), PathSegment::Index(1)]));
+        match to_writer(vec![], &bytes) {
+            Err(Error::Decode(error)) => assert_eq!(error, expected),
+            _ => panic!("expected a decode error"),
+        }
+
+        let symbol = build(|e| { e.value().tag(b"a", |e| e.write_null()).tag(b"\xff", |e| e.write_null()).payload_null(); });
+        let expected = codec::decode::<serde_json::Value>(&symbol).unwrap_err();
+        assert_eq!((expected.reason, expected.stage, expected.offset), (Reason::InvalidValue(Invalid::InvalidSymbol), Stage::Tags, 0));
+        assert_eq!(expected.path, Path(vec![PathSegment::Tag(vec![0xff])]));
+        match to_writer(vec![], &symbol) {
+            Err(Error::Decode(error)) => assert_eq!(error, expected),
+            _ => panic!("expected an invalid symbol"),
+        }
+    }
+
+    #[test]
+    fn depth_limit() {
+        let udoc = from_slice(b"[[[1]]]").unwrap();
+        let mut result = vec![];
+        to_writer_with_options(&mut result, &udoc, &DecodeOptions::new().max_depth(3)).unwrap();
+        assert_eq!(result, b"[[[1]]]");
+
+        let options = DecodeOptions::new().max_depth(2);
+        let error = match to_writer_with_options(vec![], &udoc, &options) {
+            Err(Error::Decode(error)) => error,
+            _ => panic!("expected a depth limit"),
+        };
+        assert_eq!(error.reason, Reason::DepthLimit);
+        assert_eq!(error.path, Path(vec![PathSegment::Index(0), PathSegment::Index(0)]));
+        assert_eq!(codec::decode_with_options::<serde_json::Value>(&udoc, &options).err(), Some(error));
+    }
+}
@@ -2,42 +2,162 @@ pub mod wire_type;
 pub mod utils;
 pub mod encoder;
 pub mod decoder;
+pub mod stream;
+pub mod value;
+pub mod codec;
+pub mod decimal;
+pub mod dictionary;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "num-bigint")]
+mod bigint;
+#[cfg(feature = "json")]
+pub mod json;
 
 pub use wire_type::*;
+pub use decoder::DecodeError;
 pub use slice_reader::Reader;
+pub use codec::{Encode, Decode};
+pub use decimal::{Decimal32, Decimal64, DecimalError};
+pub use dictionary::{Dictionary, Dictionaries};
+#[cfg(feature = "derive")]
+pub use udoc_derive::{UdocEncode, UdocDecode};
+#[cfg(feature = "serde")]
+pub use ser::to_vec;
+#[cfg(feature = "serde")]
+pub use de::{from_slice, from_slice_with_options};
 
+use std::collections::HashSet;
+use decoder::{Stage, Reason, DecodeOptions};
+use utils::offset_in;
 
 
-pub fn validate(buffer: &[u8]) -> Result<(), ()> {
+
+// how `validate` treats the symbols of a tags block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagPolicy {
+    AllowDuplicates,
+    RejectDuplicates,
+    // strictly ascending by their bytes, which also rejects duplicates.
+    Sorted,
+}
+
+// the default options accept any well formed document nested no deeper
+// than `DecodeOptions::DEFAULT_MAX_DEPTH`.
+#[derive(Debug, Clone, Copy)]
+pub struct ValidationOptions<'dict> {
+    decode:          DecodeOptions<'dict>,
+    tags:            TagPolicy,
+    max_list_length: usize,
+    max_size:        usize,
+}
+
+impl ValidationOptions<'static> {
+    pub fn new() -> ValidationOptions<'static> {
+        ValidationOptions {
+            decode:          DecodeOptions::new(),
+            tags:            TagPolicy::AllowDuplicates,
+            max_list_length: usize::MAX,
+            max_size:        usize::MAX,
+        }
+    }
+}
+
+impl<'dict> ValidationOptions<'dict> {
+    pub fn dictionaries<'new>(self, dictionaries: &'new Dictionaries) -> ValidationOptions<'new> {
+        let ValidationOptions { decode, tags, max_list_length, max_size } = self;
+        ValidationOptions { decode: decode.dictionaries(dictionaries), tags, max_list_length, max_size }
+    }
+
+    pub fn tags(self, tags: TagPolicy) -> ValidationOptions<'dict> {
+        ValidationOptions { tags, ..self }
+    }
+
+    // see `DecodeOptions::max_depth`. validation doesn't recurse, any limit
+    // is safe.
+    pub fn max_depth(self, max_depth: usize) -> ValidationOptions<'dict> {
+        ValidationOptions { decode: self.decode.max_depth(max_depth), ..self }
+    }
+
+    pub fn max_list_length(self, max_list_length: usize) -> ValidationOptions<'dict> {
+        ValidationOptions { max_list_length, ..self }
+    }
+
+    // in bytes, including the preamble.
+    pub fn max_size(self, max_size: usize) -> ValidationOptions<'dict> {
+        ValidationOptions { max_size, ..self }
+    }
+}
+
+impl Default for ValidationOptions<'static> {
+    fn default() -> ValidationOptions<'static> {
+        ValidationOptions::new()
+    }
+}
+
+
+pub fn validate(buffer: &[u8]) -> Result<(), DecodeError> {
+    validate_with_options(buffer, &ValidationOptions::new())
+}
+
+// a violated policy is reported by the error's `reason`.
+pub fn validate_with_options(buffer: &[u8], options: &ValidationOptions) -> Result<(), DecodeError> {
     let mut reader = slice_reader::Reader::new(buffer);
-    _validate(&decoder::decode_value(&mut reader).ok_or(())?)?;
+    if buffer.len() > options.max_size {
+        return Err(DecodeError {
+            expected: options.max_size,
+            available: buffer.len(),
+            ..DecodeError::new(Stage::Preamble, Reason::DocumentTooLarge, &reader)
+        });
+    }
+
+    _validate(&decoder::decode_document_with_options(&mut reader, &options.decode)?, options)?;
     if reader.has_some() {
-        return Err(())
+        return Err(DecodeError::new(Stage::Trailing, Reason::TrailingBytes, &reader));
     }
     Ok(())
 }
 
-pub fn _validate(value: &decoder::Value) -> Result<(), ()> {
-    if value.header.has_tags {
-        let mut tags = value.tags().ok_or(())?;
-        for (_symbol, value) in &mut tags {
-            _validate(&value)?;
-        }
-        tags.check_error()?;
+// error offsets are relative to the document's root. the depth limit is
+// the one `value` was decoded with.
+// note: nested values are visited by a `Walker`, without recursion.
+pub fn _validate(value: &decoder::Value, options: &ValidationOptions) -> Result<(), DecodeError> {
+    let mut walker = decoder::Walker::new(*value);
+    while let Some(next) = walker.next() {
+        let value = next?;
+        check_value(&value, options).map_err(|e| walker.annotate(e))?;
+    }
+    Ok(())
+}
+
+fn check_value(value: &decoder::Value, options: &ValidationOptions) -> Result<(), DecodeError> {
+    let root = value.document.root;
+    if value.header.has_tags && options.tags != TagPolicy::AllowDuplicates {
+        check_tags(value, options.tags)?;
     }
 
     use decoder::Payload::*;
     match value.payload {
-        String (value) => {
-            std::str::from_utf8(value).ok().ok_or(())?;
+        String (string) => {
+            if let Err(e) = std::str::from_utf8(string) {
+                let base = offset_in(root, string);
+                return Err(DecodeError::invalid_utf8(value.header, string, e).rebase(base));
+            }
         },
 
-        List (value) => {
-            let mut payload = decoder::ListDecoder::new(value).ok_or(())?;
-            for value in &mut payload {
-                _validate(&value)?;
+        List (list) => {
+            // note: a malformed length is reported by the walker.
+            if let Ok((length, reader)) = decoder::decode_length_prefixed(list) {
+                if length > options.max_list_length {
+                    return Err(DecodeError {
+                        expected:  options.max_list_length,
+                        available: length,
+                        ..DecodeError::new(Stage::Payload, Reason::ListTooLong, &reader)
+                    }.with_header(value.header).rebase(offset_in(root, list)));
+                }
             }
-            payload.check_error()?;
         },
 
         _ => (),
@@ -46,3 +166,165 @@ pub fn _validate(value: &decoder::Value) -> Result<(), ()> {
     Ok(())
 }
 
+// the symbols only, the tag values are checked when the walker visits them.
+fn check_tags(value: &decoder::Value, policy: TagPolicy) -> Result<(), DecodeError> {
+    let mut tags = value.tags()?;
+    let mut seen = HashSet::new();
+    let mut previous: Option<&[u8]> = None;
+    loop {
+        let offset = tags.base + tags.reader.cursor;
+        let symbol = match tags.next() {
+            Some((symbol, _)) => symbol,
+            None              => break,
+        };
+
+        let violation = match policy {
+            TagPolicy::AllowDuplicates  => None,
+            TagPolicy::RejectDuplicates => (!seen.insert(symbol)).then_some(Reason::DuplicateTag),
+            TagPolicy::Sorted => match previous {
+                Some(previous) if previous == symbol => Some(Reason::DuplicateTag),
+                Some(previous) if previous >  symbol => Some(Reason::UnsortedTags),
+                _ => None,
+            },
+        };
+        if let Some(reason) = violation {
+            let error = DecodeError { offset, ..DecodeError::new(Stage::Tags, reason, &tags.reader) };
+            return Err(error.in_tag(symbol));
+        }
+        previous = Some(symbol);
+    }
+    tags.check_error()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decoder::{Path, PathSegment};
+    use encoder::Encoder;
+
+    fn build<F: FnOnce(&mut Encoder)>(f: F) -> Vec<u8> {
+        let mut encoder = Encoder::default();
+        f(&mut encoder);
+        encoder.build().unwrap()
+    }
+
+    // the offset of the `index`th occurrence of `needle`.
+    fn find(bytes: &[u8], needle: &[u8], index: usize) -> usize {
+        bytes.windows(needle.len()).enumerate()
+            .filter(|(_, window)| *window == needle)
+            .nth(index).unwrap().0
+    }
+
+    // `symbols` as the tags of the second element of a list.
+    fn nested_tags(symbols: &[&[u8]]) -> Vec<u8> {
+        build(|e| {
+            e.value().payload_list(2, |e| {
+                e.write_null();
+                let mut builder = e.value().with_tags();
+                for symbol in symbols {
+                    builder = builder.tag(symbol, |e| e.write_bool(true));
+                }
+                builder.payload_null();
+            });
+        })
+    }
+
+    #[test]
+    fn reject_duplicates() {
+        let options = ValidationOptions::new().tags(TagPolicy::RejectDuplicates);
+
+        let bytes = nested_tags(&[b"alpha", b"beta"]);
+        validate_with_options(&bytes, &options).unwrap();
+
+        // unsorted is fine.
+        let bytes = nested_tags(&[b"gamma", b"alpha", b"beta"]);
+        validate_with_options(&bytes, &options).unwrap();
+
+        let bytes = nested_tags(&[b"alpha", b"beta", b"alpha", b"gamma"]);
+        validate(&bytes).unwrap();
+        let error = validate_with_options(&bytes, &options).unwrap_err();
+        assert_eq!(error.reason, Reason::DuplicateTag);
+        assert_eq!(error.stage, Stage::Tags);
+        // at the second occurrence's symbol size. available is what follows
+        // it in the tags block, the last tag ends the document.
+        assert_eq!(error.offset, find(&bytes, b"alpha", 1) - 1);
+        assert_eq!(error.expected, 0);
+        assert_eq!(error.available, bytes.len() - (find(&bytes, b"gamma", 0) - 1));
+        assert_eq!(error.path, Path(vec![PathSegment::Index(1), PathSegment::Tag(b"alpha".to_vec())]));
+    }
+
+    #[test]
+    fn sorted() {
+        let options = ValidationOptions::new().tags(TagPolicy::Sorted);
+
+        let bytes = nested_tags(&[b"alpha", b"beta", b"gamma"]);
+        validate_with_options(&bytes, &options).unwrap();
+
+        let bytes = nested_tags(&[b"alpha", b"gamma", b"beta"]);
+        let error = validate_with_options(&bytes, &options).unwrap_err();
+        assert_eq!(error.reason, Reason::UnsortedTags);
+        assert_eq!(error.stage, Stage::Tags);
+        assert_eq!(error.offset, find(&bytes, b"beta", 0) - 1);
+        assert_eq!(error.expected, 0);
+        assert_eq!(error.available, 0);
+        assert_eq!(error.path, Path(vec![PathSegment::Index(1), PathSegment::Tag(b"beta".to_vec())]));
+
+        let bytes = nested_tags(&[b"alpha", b"alpha"]);
+        let error = validate_with_options(&bytes, &options).unwrap_err();
+        assert_eq!(error.reason, Reason::DuplicateTag);
+        assert_eq!(error.offset, find(&bytes, b"alpha", 1) - 1);
+        assert_eq!(error.path, Path(vec![PathSegment::Index(1), PathSegment::Tag(b"alpha".to_vec())]));
+
+        // by bytes, not by length.
+        let bytes = nested_tags(&[b"b", b"aa"]);
+        assert_eq!(validate_with_options(&bytes, &options).unwrap_err().reason, Reason::UnsortedTags);
+    }
+
+    #[test]
+    fn max_list_length() {
+        let bytes = build(|e| {
+            e.value().with_tags()
+                .tag(b"short", |e| { e.value().payload_list(2, |e| { e.write_bool(true); e.write_bool(false); }); })
+                .tag(b"long", |e| {
+                    e.value().payload_list(3, |e| {
+                        e.write_string("first");
+                        e.write_null();
+                        e.write_null();
+                    });
+                })
+                .payload_null();
+        });
+
+        validate_with_options(&bytes, &ValidationOptions::new().max_list_length(3)).unwrap();
+
+        let error = validate_with_options(&bytes, &ValidationOptions::new().max_list_length(2)).unwrap_err();
+        assert_eq!(error.reason, Reason::ListTooLong);
+        assert_eq!(error.stage, Stage::Payload);
+        // after the list's count, at its first element.
+        assert_eq!(error.offset, find(&bytes, b"first", 0) - 2);
+        assert_eq!(error.expected, 2);
+        assert_eq!(error.available, 3);
+        assert_eq!(error.wire_type, Some(WireType::List));
+        assert_eq!(error.path, Path(vec![PathSegment::Tag(b"long".to_vec())]));
+
+        let error = validate_with_options(&bytes, &ValidationOptions::new().max_list_length(0)).unwrap_err();
+        assert_eq!(error.path, Path(vec![PathSegment::Tag(b"short".to_vec())]));
+        assert_eq!((error.expected, error.available), (0, 2));
+    }
+
+    #[test]
+    fn max_size() {
+        let bytes = build(|e| e.write_string("0123456789"));
+
+        validate_with_options(&bytes, &ValidationOptions::new().max_size(bytes.len())).unwrap();
+
+        let error = validate_with_options(&bytes, &ValidationOptions::new().max_size(bytes.len() - 1)).unwrap_err();
+        assert_eq!(error.reason, Reason::DocumentTooLarge);
+        assert_eq!(error.stage, Stage::Preamble);
+        assert_eq!(error.offset, 0);
+        assert_eq!(error.expected, bytes.len() - 1);
+        assert_eq!(error.available, bytes.len());
+        assert_eq!(error.path, Path::default());
+    }
+}
@@ -0,0 +1,656 @@
+use serde::ser::{self, Serialize};
+use crate::encoder::{self, Encoder, ValueBuilder, DeferredLength};
+
+
+// the data model mapping:
+//  - structs and maps are `Null` values with tags. map keys must be strings.
+//  - sequences, tuples and tuple structs are lists.
+//  - enum variants are written with the variant name as the kind symbol.
+//    unit variants are `Null`, newtype variants are single element lists,
+//    tuple variants are lists, and struct variants are `Null` with tags.
+//  - integers use the narrowest `Nat*`/`Int*` wire type.
+//  - `None` and `()` are `Null`, `Some(value)` is `value`.
+
+#[derive(Debug)]
+pub enum Error {
+    Message   (String),
+    KeyMustBeString,
+    Encoder   (encoder::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Message (message) => write!(f, "{}", message),
+            Error::KeyMustBeString   => write!(f, "map keys must be strings"),
+            Error::Encoder (error)   => write!(f, "encoder error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(message: T) -> Error {
+        Error::Message(message.to_string())
+    }
+}
+
+impl From<encoder::Error> for Error {
+    fn from(error: encoder::Error) -> Error {
+        Error::Encoder(error)
+    }
+}
+
+
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut encoder = Encoder::default();
+    value.serialize(Serializer::new(&mut encoder))?;
+    Ok(encoder.build()?)
+}
+
+
+pub struct Serializer<'enc> {
+    encoder: &'enc mut Encoder,
+}
+
+impl<'enc> Serializer<'enc> {
+    pub fn new(encoder: &'enc mut Encoder) -> Serializer<'enc> {
+        Serializer { encoder }
+    }
+}
+
+impl<'enc> ser::Serializer for Serializer<'enc> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq           = SerializeList<'enc>;
+    type SerializeTuple         = SerializeList<'enc>;
+    type SerializeTupleStruct   = SerializeList<'enc>;
+    type SerializeTupleVariant  = SerializeList<'enc>;
+    type SerializeMap           = SerializeTags<'enc>;
+    type SerializeStruct        = SerializeTags<'enc>;
+    type SerializeStructVariant = SerializeTags<'enc>;
+
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        self.encoder.write_bool(value);
+        Ok(())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        self.serialize_i128(value as i128)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        self.serialize_i128(value as i128)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        self.serialize_i128(value as i128)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        self.serialize_i128(value as i128)
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<(), Error> {
+        self.encoder.write_signed(value);
+        Ok(())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        self.serialize_u128(value as u128)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        self.serialize_u128(value as u128)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        self.serialize_u128(value as u128)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        self.serialize_u128(value as u128)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<(), Error> {
+        self.encoder.write_unsigned(value);
+        Ok(())
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), Error> {
+        self.encoder.write_f32(value);
+        Ok(())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), Error> {
+        self.encoder.write_f64(value);
+        Ok(())
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        self.encoder.write_string(value.encode_utf8(&mut [0; 4]));
+        Ok(())
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        self.encoder.write_string(value);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+        self.encoder.write_bytes(value);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.encoder.write_null();
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.encoder.write_null();
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.encoder.write_null();
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<(), Error> {
+        self.encoder.value().kind(variant.as_bytes()).payload_null();
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<(), Error> {
+        let mut result = Ok(());
+        self.encoder.value().kind(variant.as_bytes()).payload_list(1, |encoder| {
+            result = value.serialize(Serializer::new(encoder));
+        });
+        result
+    }
+
+    fn serialize_seq(self, length: Option<usize>) -> Result<SerializeList<'enc>, Error> {
+        Ok(SerializeList::new(self.encoder, None, length))
+    }
+
+    fn serialize_tuple(self, length: usize) -> Result<SerializeList<'enc>, Error> {
+        Ok(SerializeList::new(self.encoder, None, Some(length)))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, length: usize) -> Result<SerializeList<'enc>, Error> {
+        Ok(SerializeList::new(self.encoder, None, Some(length)))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, length: usize) -> Result<SerializeList<'enc>, Error> {
+        Ok(SerializeList::new(self.encoder, Some(variant.as_bytes()), Some(length)))
+    }
+
+    fn serialize_map(self, _length: Option<usize>) -> Result<SerializeTags<'enc>, Error> {
+        Ok(SerializeTags::new(self.encoder.value()))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _length: usize) -> Result<SerializeTags<'enc>, Error> {
+        Ok(SerializeTags::new(self.encoder.value()))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _length: usize) -> Result<SerializeTags<'enc>, Error> {
+        let builder = self.encoder.value().kind(variant.as_bytes());
+        Ok(SerializeTags::new(builder))
+    }
+}
+
+
+// `length` is the serializer's length hint, `written` the number of
+// elements written so far.
+pub struct SerializeList<'enc> {
+    encoder:  &'enc mut Encoder,
+    deferred: Option<DeferredLength>,
+    length:   usize,
+    written:  usize,
+}
+
+impl<'enc> SerializeList<'enc> {
+    fn new(encoder: &'enc mut Encoder, kind: Option<&[u8]>, length: Option<usize>) -> SerializeList<'enc> {
+        let mut builder = encoder.value();
+        if let Some(kind) = kind {
+            builder = builder.kind(kind);
+        }
+
+        match length {
+            Some(length) => {
+                builder.begin_list(length);
+                SerializeList { encoder, deferred: None, length, written: 0 }
+            },
+            None => {
+                let deferred = builder.begin_list_deferred();
+                SerializeList { encoder, deferred: Some(deferred), length: 0, written: 0 }
+            },
+        }
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.written += 1;
+        value.serialize(Serializer::new(self.encoder))
+    }
+
+    // a length hint that doesn't match the elements written is an error.
+    // it is also recorded on the encoder, so `build` fails as well.
+    fn end(self) -> Result<(), Error> {
+        match self.deferred {
+            Some(deferred) => self.encoder.end_list_deferred(deferred, self.written)?,
+            None => {
+                if self.written != self.length {
+                    let error = encoder::Error::CountMismatch { expected: self.length, written: self.written };
+                    self.encoder.fail(error.clone());
+                    return Err(error.into());
+                }
+                self.encoder.end_list()?
+            },
+        }
+        Ok(())
+    }
+}
+
+impl<'enc> ser::SerializeSeq for SerializeList<'enc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        SerializeList::end(self)
+    }
+}
+
+impl<'enc> ser::SerializeTuple for SerializeList<'enc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        SerializeList::end(self)
+    }
+}
+
+impl<'enc> ser::SerializeTupleStruct for SerializeList<'enc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        SerializeList::end(self)
+    }
+}
+
+impl<'enc> ser::SerializeTupleVariant for SerializeList<'enc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        SerializeList::end(self)
+    }
+}
+
+
+pub struct SerializeTags<'enc> {
+    builder: Option<ValueBuilder<'enc>>,
+    key:     Option<Vec<u8>>,
+}
+
+impl<'enc> SerializeTags<'enc> {
+    fn new(builder: ValueBuilder<'enc>) -> SerializeTags<'enc> {
+        SerializeTags { builder: Some(builder.with_tags()), key: None }
+    }
+
+    fn tag<T: Serialize + ?Sized>(&mut self, symbol: &[u8], value: &T) -> Result<(), Error> {
+        let mut result = Ok(());
+        let builder = self.builder.take().unwrap().tag(symbol, |encoder| {
+            result = value.serialize(Serializer::new(encoder));
+        });
+        self.builder = Some(builder);
+        result
+    }
+
+    fn end(mut self) -> Result<(), Error> {
+        self.builder.take().unwrap().payload_null();
+        Ok(())
+    }
+}
+
+impl<'enc> ser::SerializeMap for SerializeTags<'enc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().ok_or_else(|| Error::Message("value without key".into()))?;
+        self.tag(&key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        SerializeTags::end(self)
+    }
+}
+
+impl<'enc> ser::SerializeStruct for SerializeTags<'enc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.tag(key.as_bytes(), value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        SerializeTags::end(self)
+    }
+}
+
+impl<'enc> ser::SerializeStructVariant for SerializeTags<'enc> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.tag(key.as_bytes(), value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        SerializeTags::end(self)
+    }
+}
+
+
+// map keys become tag symbols.
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    type SerializeSeq           = ser::Impossible<Vec<u8>, Error>;
+    type SerializeTuple         = ser::Impossible<Vec<u8>, Error>;
+    type SerializeTupleStruct   = ser::Impossible<Vec<u8>, Error>;
+    type SerializeTupleVariant  = ser::Impossible<Vec<u8>, Error>;
+    type SerializeMap           = ser::Impossible<Vec<u8>, Error>;
+    type SerializeStruct        = ser::Impossible<Vec<u8>, Error>;
+    type SerializeStructVariant = ser::Impossible<Vec<u8>, Error>;
+
+    fn serialize_str(self, value: &str) -> Result<Vec<u8>, Error> {
+        Ok(value.as_bytes().to_vec())
+    }
+
+    fn serialize_char(self, value: char) -> Result<Vec<u8>, Error> {
+        Ok(value.encode_utf8(&mut [0; 4]).as_bytes().to_vec())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Vec<u8>, Error> {
+        Ok(variant.as_bytes().to_vec())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Vec<u8>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_i8(self, _: i8) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_i16(self, _: i16) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_i32(self, _: i32) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_i64(self, _: i64) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_u8(self, _: u8) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_u16(self, _: u16) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_u32(self, _: u32) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_u64(self, _: u64) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_f32(self, _: f32) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_f64(self, _: f64) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_bytes(self, _: &[u8]) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_none(self) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+    fn serialize_unit(self) -> Result<Vec<u8>, Error> { Err(Error::KeyMustBeString) }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<Vec<u8>, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Vec<u8>, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _: &'static str, _: u32, _: &'static str, _: &T) -> Result<Vec<u8>, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_tuple_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::KeyMustBeString)
+    }
+
+    fn serialize_struct_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::KeyMustBeString)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use serde::Serialize;
+    use serde::ser::SerializeSeq;
+    use crate::encoder::EncoderOptions;
+    use crate::value::{self, OwnedValue, OwnedPayload};
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> OwnedValue {
+        let bytes = to_vec(value).unwrap();
+        crate::validate(&bytes).unwrap();
+        value::decode_owned(&bytes).unwrap()
+    }
+
+    fn null() -> OwnedValue {
+        OwnedValue::new(OwnedPayload::Null)
+    }
+
+    fn list(values: Vec<OwnedValue>) -> OwnedValue {
+        OwnedValue::new(OwnedPayload::List(values))
+    }
+
+    fn with_kind(kind: &str, value: OwnedValue) -> OwnedValue {
+        OwnedValue { kind: Some(kind.as_bytes().to_vec()), ..value }
+    }
+
+    fn with_tags(tags: Vec<(&str, OwnedValue)>) -> OwnedValue {
+        let tags = tags.into_iter().map(|(symbol, value)| (symbol.as_bytes().to_vec(), value)).collect();
+        OwnedValue { tags: Some(tags), ..null() }
+    }
+
+    fn nat8(value: u8) -> OwnedValue {
+        OwnedValue::new(OwnedPayload::Nat8(value))
+    }
+
+    fn string(value: &str) -> OwnedValue {
+        OwnedValue::new(OwnedPayload::String(value.into()))
+    }
+
+
+    #[derive(Serialize)]
+    struct Point {
+        x: u8,
+        y: i32,
+        #[serde(rename = "point-label")]
+        label: Option<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Meters(f64);
+
+    #[derive(Serialize)]
+    struct Pair(u8, &'static str);
+
+    #[derive(Serialize)]
+    struct Unit;
+
+    #[derive(Serialize)]
+    enum Shape {
+        Empty,
+        Circle(u8),
+        Rectangle(u8, u8),
+        Polygon { closed: bool },
+    }
+
+    // a sequence of unknown length, like an iterator.
+    struct Unsized(Vec<u8>);
+
+    impl Serialize for Unsized {
+        fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(None)?;
+            for value in &self.0 {
+                seq.serialize_element(value)?;
+            }
+            seq.end()
+        }
+    }
+
+    // a sequence whose length hint is wrong.
+    struct Hinted { hint: usize, length: usize }
+
+    impl Serialize for Hinted {
+        fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(self.hint))?;
+            for value in 0..self.length {
+                seq.serialize_element(&(value as u8))?;
+            }
+            seq.end()
+        }
+    }
+
+
+    #[test]
+    fn structs() {
+        let value = encode(&Point { x: 1, y: -2, label: None });
+        assert_eq!(value, with_tags(vec![
+            ("x", nat8(1)),
+            ("y", OwnedValue::new(OwnedPayload::Int8(-2))),
+            ("point-label", null()),
+        ]));
+
+        assert_eq!(encode(&Meters(1.5)), OwnedValue::new(OwnedPayload::Float64(1.5)));
+        assert_eq!(encode(&Pair(1, "a")), list(vec![nat8(1), string("a")]));
+        assert_eq!(encode(&Unit), null());
+        assert_eq!(encode(&()), null());
+    }
+
+    #[test]
+    fn enum_variants() {
+        assert_eq!(encode(&Shape::Empty), with_kind("Empty", null()));
+        assert_eq!(encode(&Shape::Circle(3)), with_kind("Circle", list(vec![nat8(3)])));
+        assert_eq!(encode(&Shape::Rectangle(1, 2)), with_kind("Rectangle", list(vec![nat8(1), nat8(2)])));
+        assert_eq!(encode(&Shape::Polygon { closed: true }),
+                   with_kind("Polygon", with_tags(vec![("closed", OwnedValue::new(OwnedPayload::Bool(true)))])));
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(encode(&None::<u8>), null());
+        assert_eq!(encode(&Some(3u8)), nat8(3));
+        assert_eq!(encode(&Some("x")), string("x"));
+        assert_eq!(encode(&vec![Some(1u8), None]), list(vec![nat8(1), null()]));
+    }
+
+    #[test]
+    fn maps() {
+        let map: BTreeMap<&str, u8> = [("a", 1), ("b", 2)].into_iter().collect();
+        assert_eq!(encode(&map), with_tags(vec![("a", nat8(1)), ("b", nat8(2))]));
+
+        let map: BTreeMap<char, u8> = [('c', 1)].into_iter().collect();
+        assert_eq!(encode(&map), with_tags(vec![("c", nat8(1))]));
+
+        #[derive(Serialize, PartialEq, Eq, PartialOrd, Ord)]
+        enum Key { First }
+        let map: BTreeMap<Key, u8> = [(Key::First, 1)].into_iter().collect();
+        assert_eq!(encode(&map), with_tags(vec![("First", nat8(1))]));
+
+        let map: BTreeMap<u32, u8> = [(1, 1)].into_iter().collect();
+        assert!(matches!(to_vec(&map), Err(Error::KeyMustBeString)));
+
+        let map: BTreeMap<(u8, u8), u8> = [((1, 2), 1)].into_iter().collect();
+        assert!(matches!(to_vec(&map), Err(Error::KeyMustBeString)));
+
+        let map: BTreeMap<Option<&str>, u8> = [(Some("a"), 1)].into_iter().collect();
+        assert!(matches!(to_vec(&map), Err(Error::KeyMustBeString)));
+    }
+
+    #[test]
+    fn deferred_lists() {
+        for length in [0, 1, 63, 64, 20000] {
+            let values = (0..length).map(|index| index as u8).collect::<Vec<_>>();
+            let expected = list(values.iter().map(|value| nat8(*value)).collect());
+            assert_eq!(encode(&Unsized(values.clone())), expected);
+
+            // nested in a list of known length.
+            let nested = (1u8, Unsized(values), Unsized(vec![]));
+            assert_eq!(encode(&nested), list(vec![nat8(1), expected, list(vec![])]));
+        }
+    }
+
+    #[test]
+    fn length_hints() {
+        assert_eq!(encode(&Hinted { hint: 2, length: 2 }), list(vec![nat8(0), nat8(1)]));
+
+        for options in [EncoderOptions::new(), EncoderOptions::new().checked(true)] {
+            for (hint, length) in [(3, 2), (1, 2), (0, 1), (1, 0)] {
+                let mut encoder = Encoder::new(options.clone()).unwrap();
+                let error = Hinted { hint, length }.serialize(Serializer::new(&mut encoder)).unwrap_err();
+                let expected = encoder::Error::CountMismatch { expected: hint, written: length };
+                assert!(matches!(&error, Error::Encoder(error) if *error == expected));
+                assert_eq!(encoder.build().unwrap_err(), expected);
+            }
+        }
+
+        // nested in a list that is otherwise fine.
+        let error = to_vec(&vec![Hinted { hint: 1, length: 1 }, Hinted { hint: 5, length: 4 }]).unwrap_err();
+        assert!(matches!(error, Error::Encoder(encoder::Error::CountMismatch { expected: 5, written: 4 })));
+    }
+}
@@ -0,0 +1,289 @@
+use std::io::{self, Write};
+use slice_reader::Reader;
+use crate::encoder::{self, Encoder, EncoderOptions};
+use crate::decoder::{self, DecodeError, DecodeOptions, Value};
+
+
+// a stream is a concatenation of documents, without separators. each
+// document is complete on its own: its preamble, if it has one, applies to
+// its root value only. documents are self delimiting, the end of one is the
+// end of its root value.
+
+
+#[derive(Debug)]
+pub enum Error {
+    Io      (io::Error),
+    Encoder (encoder::Error),
+    // a value written with raw `append_*` calls only, which can't be counted.
+    RawValue,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Io      (error) => write!(f, "{}", error),
+            Error::Encoder (error) => write!(f, "encoder error: {}", error),
+            Error::RawValue        => write!(f, "root value written without Encoder::value"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<encoder::Error> for Error {
+    fn from(error: encoder::Error) -> Error {
+        Error::Encoder(error)
+    }
+}
+
+
+// writes a stream of documents to `writer`, one per value.
+// each value is written as soon as it is complete. it can't be written
+// earlier: sizes precede the bytes they cover, and the preamble precedes the
+// root value. so the current value is buffered in the encoder, and written
+// from there, with its sizes compressed or widened on the way, without
+// another copy. one large root value is never streamed, large exports
+// should be split into many root values.
+// note: the writer gets many small writes, wrap unbuffered writers in a
+// `BufWriter`.
+pub struct StreamEncoder<W: Write> {
+    writer:  W,
+    encoder: Encoder,
+}
+
+impl<W: Write> StreamEncoder<W> {
+    pub fn new(writer: W, options: EncoderOptions) -> Result<StreamEncoder<W>, Error> {
+        Ok(StreamEncoder { writer, encoder: Encoder::new(options)? })
+    }
+
+    // `value` must write exactly one root value, started with
+    // `Encoder::value` or a `write_*` method. raw `append_*` calls are for
+    // the insides of values: at the root they aren't counted, a value
+    // written with them alone fails with `Error::RawValue`, and mixing them
+    // with a counted value isn't checked. other mistakes fail with
+    // `encoder::Error::CountMismatch` and the like, and nothing is written.
+    // if the writer fails, part of the document may have been written.
+    // the encoder is reset afterwards, even if writing fails.
+    pub fn write_value<F: FnOnce(&mut Encoder)>(&mut self, value: F) -> Result<(), Error> {
+        value(&mut self.encoder);
+
+        let result = self.check_value()
+            .and_then(|()| Ok(self.encoder.write_document(&mut self.writer)?));
+        self.encoder.reset();
+        result
+    }
+
+    fn check_value(&self) -> Result<(), Error> {
+        self.encoder.check_build()?;
+        match self.encoder.roots() {
+            1 => Ok(()),
+            0 if self.encoder.size()? > 0 => Err(Error::RawValue),
+            written => Err(encoder::Error::CountMismatch { expected: 1, written }.into()),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+
+// the root values of the documents in `buffer`, in order.
+pub fn decode_documents(buffer: &[u8]) -> Documents<'_> {
+    decode_documents_with_options(buffer, &DecodeOptions::new())
+}
+
+pub fn decode_documents_with_options<'a>(buffer: &'a [u8], options: &DecodeOptions<'a>) -> Documents<'a> {
+    Documents { reader: Reader::new(buffer), options: *options, failed: false }
+}
+
+// note: error offsets are relative to the stream. iteration stops at the
+// first error.
+pub struct Documents<'a> {
+    reader:  Reader<'a, u8>,
+    options: DecodeOptions<'a>,
+    failed:  bool,
+}
+
+impl<'a> Iterator for Documents<'a> {
+    type Item = Result<Value<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Result<Value<'a>, DecodeError>> {
+        if self.failed || !self.reader.has_some() {
+            return None;
+        }
+        let result = decoder::decode_document_with_options(&mut self.reader, &self.options);
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::dictionary::{Dictionary, Dictionaries};
+    use crate::decoder::Reason;
+    use crate::wire_type::WireType;
+    use crate::value::{self, OwnedValue, OwnedPayload};
+
+    fn string(value: &str) -> OwnedValue {
+        OwnedValue::new(OwnedPayload::String(value.into()))
+    }
+
+    fn symbols(values: &[&[u8]]) -> OwnedValue {
+        OwnedValue::new(OwnedPayload::List(values.iter().map(|value| OwnedValue::new(OwnedPayload::Symbol(value.to_vec()))).collect()))
+    }
+
+    fn owned(documents: Documents) -> Vec<Result<OwnedValue, DecodeError>> {
+        documents.map(|document| document.and_then(|value| value.into_owned())).collect()
+    }
+
+    fn write_symbols(encoder: &mut Encoder, values: &[&[u8]]) {
+        encoder.value().payload_list(values.len(), |encoder| {
+            for value in values {
+                encoder.write_symbol(value);
+            }
+        });
+    }
+
+    #[test]
+    fn round_trip() {
+        let dictionary = Arc::new(Dictionary::new(7, 1, ["shared"]));
+        let options = EncoderOptions::new().intern_symbols(true).dictionary(dictionary.clone());
+        let mut stream = StreamEncoder::new(vec![], options.clone()).unwrap();
+
+        // each document has its own symbol table.
+        let values: [&[&[u8]]; 4] = [
+            &[b"a", b"a", b"shared"],
+            &[],
+            &[b"b", b"b", b"b"],
+            &[b"a", b"b", b"a", b"b"],
+        ];
+        let mut expected = vec![];
+        for symbols in values {
+            stream.write_value(|encoder| write_symbols(encoder, symbols)).unwrap();
+
+            let mut encoder = Encoder::new(options.clone()).unwrap();
+            write_symbols(&mut encoder, symbols);
+            expected.extend(encoder.build().unwrap());
+        }
+        stream.write_value(|encoder| encoder.write_string("last")).unwrap();
+        stream.flush().unwrap();
+        let bytes = stream.into_inner();
+        assert_eq!(bytes[..expected.len()], expected);
+
+        let mut dictionaries = Dictionaries::new();
+        dictionaries.register(dictionary);
+        let options = DecodeOptions::new().dictionaries(&dictionaries);
+        let documents = owned(decode_documents_with_options(&bytes, &options)).into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        let mut expected = values.iter().map(|values| symbols(values)).collect::<Vec<_>>();
+        expected.push(string("last"));
+        assert_eq!(documents, expected);
+
+        // the dictionary is required.
+        let documents = owned(decode_documents(&bytes));
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].as_ref().unwrap_err().reason, Reason::UnknownDictionary { id: 7, version: 1 });
+
+        assert!(decode_documents(&[]).next().is_none());
+    }
+
+    #[test]
+    fn root_count() {
+        let mut stream = StreamEncoder::new(vec![], EncoderOptions::new()).unwrap();
+
+        let error = stream.write_value(|_| ()).unwrap_err();
+        assert!(matches!(error, Error::Encoder(encoder::Error::CountMismatch { expected: 1, written: 0 })));
+
+        let error = stream.write_value(|encoder| {
+            encoder.write_string("a");
+            encoder.write_string("b");
+        }).unwrap_err();
+        assert!(matches!(error, Error::Encoder(encoder::Error::CountMismatch { expected: 1, written: 2 })));
+
+        // nested values don't count.
+        stream.write_value(|encoder| {
+            encoder.value().with_tags().tag(b"a", |encoder| encoder.write_null()).payload_list(2, |encoder| {
+                encoder.write_null();
+                encoder.write_null();
+            });
+        }).unwrap();
+
+        // nothing was written for the failed values, and the stream goes on.
+        stream.write_value(|encoder| encoder.write_string("c")).unwrap();
+        let bytes = stream.into_inner();
+        let documents = owned(decode_documents(&bytes));
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[1], Ok(string("c")));
+    }
+
+    #[test]
+    fn raw_values() {
+        let mut stream = StreamEncoder::new(vec![], EncoderOptions::new()).unwrap();
+        let error = stream.write_value(|encoder| {
+            encoder.append_byte(WireType::String as u8);
+            encoder.append_size_prefixed(b"raw");
+        }).unwrap_err();
+        assert!(matches!(error, Error::RawValue));
+
+        // raw writes inside a value are fine.
+        stream.write_value(|encoder| {
+            encoder.value().with_tags().tag(b"a", |encoder| {
+                encoder.append_byte(WireType::String as u8);
+                encoder.append_size_prefixed(b"raw");
+            }).payload_null();
+        }).unwrap();
+
+        stream.write_value(|encoder| encoder.write_null()).unwrap();
+        let bytes = stream.into_inner();
+        let tagged = OwnedValue { tags: Some(vec![(b"a".to_vec(), string("raw"))]), ..OwnedValue::new(OwnedPayload::Null) };
+        assert_eq!(owned(decode_documents(&bytes)), vec![Ok(tagged), Ok(OwnedValue::new(OwnedPayload::Null))]);
+    }
+
+    #[test]
+    fn truncated() {
+        let mut stream = StreamEncoder::new(vec![], EncoderOptions::new()).unwrap();
+        stream.write_value(|encoder| encoder.write_string("first")).unwrap();
+        stream.write_value(|encoder| encoder.write_string("second")).unwrap();
+        let bytes = stream.into_inner();
+        let first = value::decode_owned(&bytes[..7]).unwrap();
+        assert_eq!(first, string("first"));
+
+        // cut in the second document's string.
+        // iteration stops at the error.
+        let documents = owned(decode_documents(&bytes[..bytes.len() - 2]));
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0], Ok(string("first")));
+
+        let error = documents[1].as_ref().unwrap_err();
+        assert_eq!(error.reason, Reason::UnexpectedEnd);
+        // relative to the stream, after the second document's size.
+        assert_eq!(error.offset, 7 + 2);
+        assert_eq!(error.expected, 6);
+        assert_eq!(error.available, 4);
+
+        // cut after a header.
+        let documents = owned(decode_documents(&bytes[..8]));
+        assert_eq!(documents.len(), 2);
+        let error = documents[1].as_ref().unwrap_err();
+        assert_eq!(error.reason, Reason::UnexpectedEnd);
+        assert_eq!(error.offset, 8);
+    }
+}
@@ -6,7 +6,7 @@ pub fn encode_size<B: ByteOrder>(value: u64) -> ([u8; 8], usize) {
 
     let value = value << 2;
     let (value, length) =
-        if      bits <=  8 - 2 { (value | 0b00, 1) }
+        if      bits <=  8 - 2 { (value,        1) }
         else if bits <= 16 - 2 { (value | 0b01, 2) }
         else if bits <= 32 - 2 { (value | 0b10, 4) }
         else if bits <= 64 - 2 { (value | 0b11, 8) }
@@ -15,6 +15,19 @@ pub fn encode_size<B: ByteOrder>(value: u64) -> ([u8; 8], usize) {
     (B::write_u64(value), length)
 }
 
+// encodes `value` using exactly `length` bytes, which must be large enough.
+pub fn encode_size_fixed<B: ByteOrder>(value: u64, length: usize) -> [u8; 8] {
+    let value = value << 2;
+    let value = match length {
+        1 => value,
+        2 => value | 0b01,
+        4 => value | 0b10,
+        8 => value | 0b11,
+        _ => unreachable!()
+    };
+    B::write_u64(value)
+}
+
 pub fn decode_size<B: ByteOrder>(reader: &mut Reader<u8>) -> Option<u64> {
     let first = *reader.peek()?;
     let value = match first & 0b11 {
@@ -43,3 +56,80 @@ pub fn decode_size_as_usize<B: ByteOrder>(reader: &mut Reader<u8>) -> Option<usi
     u64_to_usize(decode_size::<B>(reader)?)
 }
 
+
+// the offset of `slice` in `root`, which `slice` must be borrowed from.
+pub fn offset_in(root: &[u8], slice: &[u8]) -> usize {
+    slice.as_ptr() as usize - root.as_ptr() as usize
+}
+
+
+// `Nat` is the little endian magnitude, `Int` the little endian two's
+// complement. both may have redundant high bytes.
+
+pub fn nat_to_u128(bytes: &[u8]) -> Option<u128> {
+    let mut result = [0; 16];
+    for (i, byte) in bytes.iter().enumerate() {
+        if i < 16          { result[i] = *byte }
+        else if *byte != 0 { return None }
+    }
+    Some(u128::from_le_bytes(result))
+}
+
+pub fn int_to_i128(bytes: &[u8]) -> Option<i128> {
+    let negative = bytes.last().is_some_and(|byte| byte & 0x80 != 0);
+    let fill = if negative { 0xff } else { 0 };
+
+    let mut result = [fill; 16];
+    for (i, byte) in bytes.iter().enumerate() {
+        if i < 16          { result[i] = *byte }
+        else if *byte != fill { return None }
+    }
+
+    let result = i128::from_le_bytes(result);
+    if (result < 0) != negative {
+        return None;
+    }
+    Some(result)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nat_layout() {
+        assert_eq!(nat_to_u128(&[]), Some(0));
+        assert_eq!(nat_to_u128(&[1, 2]), Some(0x0201));
+        assert_eq!(nat_to_u128(&[5, 0, 0]), Some(5));
+        assert_eq!(nat_to_u128(&[0xff; 16]), Some(u128::MAX));
+
+        // redundant bytes past the 16th are fine, others overflow.
+        let mut bytes = [0xff; 20];
+        bytes[16..].fill(0);
+        assert_eq!(nat_to_u128(&bytes), Some(u128::MAX));
+        bytes[19] = 1;
+        assert_eq!(nat_to_u128(&bytes), None);
+    }
+
+    #[test]
+    fn int_layout() {
+        assert_eq!(int_to_i128(&[]), Some(0));
+        assert_eq!(int_to_i128(&[0xff]), Some(-1));
+        assert_eq!(int_to_i128(&[0x80]), Some(-128));
+        assert_eq!(int_to_i128(&[0x7f, 0xff]), Some(-129));
+        assert_eq!(int_to_i128(&[0x80, 0x00]), Some(128));
+        assert_eq!(int_to_i128(&[0xff; 20]), Some(-1));
+        assert_eq!(int_to_i128(&[0x7f, 0x00, 0x00, 0x00]), Some(127));
+        assert_eq!(int_to_i128(&i128::MIN.to_le_bytes()), Some(i128::MIN));
+
+        // 2^127, and -2^127 - 1.
+        let mut bytes = [0; 17];
+        bytes[15] = 0x80;
+        assert_eq!(int_to_i128(&bytes), None);
+        let mut bytes = [0xff; 17];
+        bytes[15] = 0x7f;
+        assert_eq!(int_to_i128(&bytes), None);
+    }
+}
@@ -0,0 +1,279 @@
+use slice_reader::Reader;
+use crate::{utils::*, encoder::Encoder, wire_type::WireType, decimal::{Decimal32, Decimal64}};
+use crate::decoder::{self, DecodeError, Stage, Reason, Payload, Value, DecodeOptions, ListDecoder};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedPayload {
+    Null,
+    Bool      (bool),
+    Nat       (Vec<u8>),
+    Nat8      (u8),
+    Nat16     (u16),
+    Nat32     (u32),
+    Nat64     (u64),
+    Int       (Vec<u8>),
+    Int8      (i8),
+    Int16     (i16),
+    Int32     (i32),
+    Int64     (i64),
+    Float32   (f32),
+    Float64   (f64),
+    Decimal32 (Decimal32),
+    Decimal64 (Decimal64),
+    Bytes     (Vec<u8>),
+    String    (String),
+    Symbol    (Vec<u8>),
+    List      (Vec<OwnedValue>),
+}
+
+// `kind` and `tags` are `None` if the value has no kind or tags section.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedValue {
+    pub kind:    Option<Vec<u8>>,
+    pub tags:    Option<Vec<(Vec<u8>, OwnedValue)>>,
+    pub payload: OwnedPayload,
+}
+
+impl OwnedValue {
+    pub fn new(payload: OwnedPayload) -> OwnedValue {
+        OwnedValue { kind: None, tags: None, payload }
+    }
+
+    pub fn encode(&self, encoder: &mut Encoder) {
+        let mut builder = encoder.value();
+
+        if let Some(kind) = &self.kind {
+            builder = builder.kind(kind);
+        }
+
+        if let Some(tags) = &self.tags {
+            builder = builder.with_tags();
+            for (symbol, value) in tags {
+                builder = builder.tag(symbol, |encoder| value.encode(encoder));
+            }
+        }
+
+        use OwnedPayload::*;
+        match &self.payload {
+            Null                => builder.payload_null(),
+            Bool      (value)   => builder.payload_bool(*value),
+            // as stored, `payload_nat` and `payload_int` drop redundant high bytes.
+            Nat       (value)   => builder.finish(WireType::Nat).append_size_prefixed(value),
+            Nat8      (value)   => builder.payload_nat8(*value),
+            Nat16     (value)   => builder.payload_nat16(*value),
+            Nat32     (value)   => builder.payload_nat32(*value),
+            Nat64     (value)   => builder.payload_nat64(*value),
+            Int       (value)   => builder.finish(WireType::Int).append_size_prefixed(value),
+            Int8      (value)   => builder.payload_int8(*value),
+            Int16     (value)   => builder.payload_int16(*value),
+            Int32     (value)   => builder.payload_int32(*value),
+            Int64     (value)   => builder.payload_int64(*value),
+            Float32   (value)   => builder.payload_f32(*value),
+            Float64   (value)   => builder.payload_f64(*value),
+            Decimal32 (value)   => builder.payload_decimal32(*value),
+            Decimal64 (value)   => builder.payload_decimal64(*value),
+            Bytes     (value)   => builder.payload_bytes(value),
+            String    (value)   => builder.payload_string(value),
+            Symbol    (value)   => builder.payload_symbol(value),
+            List      (values)  => {
+                builder.payload_list(values.len(), |encoder| {
+                    for value in values {
+                        value.encode(encoder);
+                    }
+                });
+            },
+        }
+    }
+}
+
+
+pub fn decode_owned(buffer: &[u8]) -> Result<OwnedValue, DecodeError> {
+    decode_owned_with_options(buffer, &DecodeOptions::new())
+}
+
+pub fn decode_owned_with_options(buffer: &[u8], options: &DecodeOptions) -> Result<OwnedValue, DecodeError> {
+    let mut reader = Reader::new(buffer);
+    let value = to_owned(decoder::decode_document_with_options(&mut reader, options)?)?;
+    if reader.has_some() {
+        return Err(DecodeError::new(Stage::Trailing, Reason::TrailingBytes, &reader));
+    }
+    Ok(value)
+}
+
+impl<'val> Value<'val> {
+    pub fn into_owned(self) -> Result<OwnedValue, DecodeError> {
+        to_owned(self)
+    }
+}
+
+// error offsets are relative to the document's root.
+pub(crate) fn to_owned(value: Value) -> Result<OwnedValue, DecodeError> {
+    let kind = value.header.has_kind.then(|| value.kind.to_vec());
+
+    let tags =
+        if value.header.has_tags {
+            let mut tags = value.tags()?;
+
+            let mut result = Vec::with_capacity(tags.remaining);
+            for (symbol, tag) in &mut tags {
+                let tag = to_owned(tag).map_err(|e| e.in_tag(symbol))?;
+                result.push((symbol.to_vec(), tag));
+            }
+            tags.check_error()?;
+
+            Some(result)
+        }
+        else {
+            None
+        };
+
+    use OwnedPayload as P;
+    let payload = match value.payload {
+        Payload::Null                => P::Null,
+        Payload::Bool      (value)   => P::Bool(value),
+        Payload::Nat       (value)   => P::Nat(value.to_vec()),
+        Payload::Nat8      (value)   => P::Nat8(value),
+        Payload::Nat16     (value)   => P::Nat16(value),
+        Payload::Nat32     (value)   => P::Nat32(value),
+        Payload::Nat64     (value)   => P::Nat64(value),
+        Payload::Int       (value)   => P::Int(value.to_vec()),
+        Payload::Int8      (value)   => P::Int8(value),
+        Payload::Int16     (value)   => P::Int16(value),
+        Payload::Int32     (value)   => P::Int32(value),
+        Payload::Int64     (value)   => P::Int64(value),
+        Payload::Float32   (value)   => P::Float32(value),
+        Payload::Float64   (value)   => P::Float64(value),
+        Payload::Decimal32 (value)   => P::Decimal32(value),
+        Payload::Decimal64 (value)   => P::Decimal64(value),
+        Payload::Bytes     (value)   => P::Bytes(value.to_vec()),
+        Payload::Symbol    (value)   => P::Symbol(value.to_vec()),
+
+        Payload::String (string) => {
+            match std::str::from_utf8(string) {
+                Ok(string) => P::String(string.into()),
+                Err(e) => {
+                    let base = offset_in(value.document.root, string);
+                    return Err(DecodeError::invalid_utf8(value.header, string, e).rebase(base));
+                },
+            }
+        },
+
+        Payload::List (list) => {
+            let mut payload = ListDecoder::new(list, value.document, value.depth)?;
+
+            let mut values = Vec::with_capacity(payload.length);
+            for (index, value) in (&mut payload).enumerate() {
+                values.push(to_owned(value).map_err(|e| e.in_index(index))?);
+            }
+            payload.check_element_error()?;
+
+            P::List(values)
+        },
+    };
+
+    Ok(OwnedValue { kind, tags, payload })
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::{Path, PathSegment};
+
+    fn round_trip(value: &OwnedValue) {
+        let mut encoder = Encoder::default();
+        value.encode(&mut encoder);
+        let bytes = encoder.build().unwrap();
+
+        assert_eq!(&decode_owned(&bytes).unwrap(), value);
+        let root = decoder::decode_document(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(&root.into_owned().unwrap(), value);
+    }
+
+    fn payloads() -> Vec<OwnedPayload> {
+        use OwnedPayload::*;
+        vec![
+            Null,
+            Bool(true),
+            Bool(false),
+            Nat(vec![0, 0, 0, 0, 0, 0, 0, 0, 1]),
+            Nat8(u8::MAX),
+            Nat16(u16::MAX),
+            Nat32(u32::MAX),
+            Nat64(u64::MAX),
+            Int(vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 0xff]),
+            Int8(i8::MIN),
+            Int16(i16::MIN),
+            Int32(i32::MIN),
+            Int64(i64::MIN),
+            Float32(-1.5),
+            Float64(f64::MAX),
+            Decimal32(crate::decimal::Decimal32::from_parts(true, 125, -2).unwrap()),
+            Decimal64(crate::decimal::Decimal64::from_parts(false, 1, 300).unwrap()),
+            Bytes(vec![0, 0xff]),
+            String("ünïcode".into()),
+            Symbol(b"symbol".to_vec()),
+            List(vec![]),
+            List(vec![OwnedValue::new(Null), OwnedValue::new(List(vec![OwnedValue::new(Nat8(1))]))]),
+        ]
+    }
+
+    #[test]
+    fn every_payload() {
+        for payload in payloads() {
+            round_trip(&OwnedValue::new(payload.clone()));
+
+            round_trip(&OwnedValue {
+                kind:    Some(b"kind".to_vec()),
+                tags:    Some(vec![(b"a".to_vec(), OwnedValue::new(payload.clone())), (b"".to_vec(), OwnedValue::new(OwnedPayload::Null))]),
+                payload: payload.clone(),
+            });
+
+            round_trip(&OwnedValue { kind: Some(vec![]), tags: Some(vec![]), payload });
+        }
+
+        let values = payloads().into_iter().map(OwnedValue::new).collect();
+        round_trip(&OwnedValue::new(OwnedPayload::List(values)));
+    }
+
+    #[test]
+    fn redundant_high_bytes() {
+        let mut encoder = Encoder::default();
+        encoder.value().payload_list(2, |e| {
+            e.append_byte(WireType::Nat as u8);
+            e.append_size_prefixed(&[5, 0, 0]);
+            e.append_byte(WireType::Int as u8);
+            e.append_size_prefixed(&[0x80, 0xff, 0xff]);
+        });
+        let bytes = encoder.build().unwrap();
+
+        let value = decode_owned(&bytes).unwrap();
+        let mut encoder = Encoder::default();
+        value.encode(&mut encoder);
+        assert_eq!(encoder.build().unwrap(), bytes);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut encoder = Encoder::default();
+        encoder.value().payload_list(2, |e| {
+            e.write_null();
+            e.value().tag(b"t", |e| {
+                e.append_byte(WireType::String as u8);
+                e.append_size_prefixed(b"a\xff");
+            }).payload_null();
+        });
+        let bytes = encoder.build().unwrap();
+        let invalid = bytes.windows(2).position(|window| window == b"a\xff").unwrap() + 1;
+
+        let error = decode_owned(&bytes).unwrap_err();
+        assert_eq!(error.reason, Reason::InvalidUtf8);
+        assert_eq!(error.offset, invalid);
+        assert_eq!(error.path, Path(vec![PathSegment::Index(1), PathSegment::Tag(b"t".to_vec())]));
+
+        let root = decoder::decode_document(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(root.into_owned().unwrap_err(), error);
+    }
+}
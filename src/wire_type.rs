@@ -1,3 +1,9 @@
+// `Nat` and `Int` payloads are size prefixed little endian integers of any
+// length. `Nat` is the magnitude, `Int` is two's complement, so the top bit
+// of the last byte is the sign. the encoder writes no redundant high bytes,
+// which makes zero the empty payload. decoders accept redundant bytes.
+// `Decimal32` and `Decimal64` are ieee 754-2008 decimals in the binary
+// integer decimal encoding, stored little endian like the floats.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WireType {
@@ -31,11 +37,23 @@ pub const WIRE_TYPE_MASK: u8 = 32 - 1;
 pub const WIRE_FLAG_KIND: u8 = 0x40;
 pub const WIRE_FLAG_TAGS: u8 = 0x80;
 
+// a document may start with a symbol table: this byte, which is not a valid
+// header, then a size prefixed block with the symbol count, the end offset of
+// each symbol as a little endian u32, and the concatenated symbol bytes.
+// a symbol whose size has the low bit clear is `index << 1` into the table.
+pub const SYMBOL_TABLE_MARKER: u8 = 0x00;
+
+// a document encoded against a shared `Dictionary` starts with this byte,
+// which is not a valid header either, then the dictionary's id and version
+// as sizes. the dictionary's symbols take the first indices, the symbol
+// table's follow. the symbol table comes after this section.
+pub const DICTIONARY_MARKER: u8 = WIRE_FLAG_KIND;
+
 
 impl WireType {
     pub fn from_u8(value: u8) -> Option<WireType> {
-        if value >= WIRE_TYPE_MIN && value <= WIRE_TYPE_MAX {
-            return unsafe { std::mem::transmute(value) };
+        if (WIRE_TYPE_MIN..=WIRE_TYPE_MAX).contains(&value) {
+            return Some(unsafe { std::mem::transmute::<u8, WireType>(value) });
         }
         None
     }
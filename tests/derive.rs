@@ -0,0 +1,267 @@
+#![cfg(feature = "derive")]
+
+use std::collections::BTreeMap;
+use udoc::{codec, UdocEncode, UdocDecode, Encode, Decode};
+use udoc::decoder::{Path, PathSegment, Reason, Invalid, Stage};
+use udoc::encoder::Encoder;
+use udoc::value::{self, OwnedValue, OwnedPayload};
+
+
+fn round_trip<T: Encode + for<'de> Decode<'de> + PartialEq + std::fmt::Debug>(value: &T) -> OwnedValue {
+    let bytes = codec::encode(value).unwrap();
+    udoc::validate(&bytes).unwrap();
+    assert_eq!(&codec::decode::<T>(&bytes).unwrap(), value);
+    value::decode_owned(&bytes).unwrap()
+}
+
+fn tags(value: &OwnedValue) -> Vec<&[u8]> {
+    value.tags.as_ref().unwrap().iter().map(|(symbol, _)| &symbol[..]).collect()
+}
+
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+#[udoc(kind = "user")]
+struct User {
+    #[udoc(tag = "user-name")]
+    name:     String,
+    #[udoc(wire = "nat64")]
+    id:       u8,
+    #[udoc(skip)]
+    cache:    Vec<u32>,
+    #[udoc(default)]
+    nickname: Option<String>,
+    location: Point,
+    scores:   BTreeMap<String, f64>,
+}
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+struct Tuple(u16, #[udoc(skip)] bool, String);
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+#[udoc(kind = "meters")]
+struct Meters(f64);
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+struct Id(#[udoc(wire = "nat32")] u32);
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+struct Wires {
+    #[udoc(wire = "symbol")]
+    name:  Vec<u8>,
+    #[udoc(wire = "string")]
+    text:  String,
+    #[udoc(wire = "float32")]
+    ratio: f32,
+}
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+struct Unit;
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    #[udoc(kind = "rect")]
+    Rectangle(f64, f64),
+    Polygon { points: Vec<Point>, #[udoc(tag = "closed?")] closed: bool },
+}
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+struct Pair<A, B> {
+    first:  A,
+    second: B,
+}
+
+#[derive(Debug, PartialEq, UdocEncode, UdocDecode)]
+struct Borrowed<'a> {
+    name:  &'a str,
+    bytes: &'a [u8],
+}
+
+
+#[test]
+fn named_structs() {
+    let value = round_trip(&Point { x: 1, y: -2 });
+    assert_eq!(value.kind, None);
+    assert_eq!(tags(&value), [&b"x"[..], b"y"]);
+    assert_eq!(value.payload, OwnedPayload::Null);
+
+    let user = User {
+        name:     "ann".into(),
+        id:       7,
+        cache:    vec![],
+        nickname: Some("a".into()),
+        location: Point { x: 3, y: 4 },
+        scores:   [("a".to_string(), 1.5), ("b".to_string(), -2.0)].into_iter().collect(),
+    };
+    let value = round_trip(&user);
+    assert_eq!(value.kind.as_deref(), Some(&b"user"[..]));
+    assert_eq!(tags(&value), [&b"user-name"[..], b"id", b"nickname", b"location", b"scores"]);
+    let id = &value.tags.as_ref().unwrap()[1].1;
+    assert_eq!(id.payload, OwnedPayload::Nat64(7));
+
+    let value = round_trip(&Wires { name: b"n".to_vec(), text: "t".into(), ratio: 0.5 });
+    let payloads = value.tags.as_ref().unwrap().iter().map(|(_, value)| value.payload.clone()).collect::<Vec<_>>();
+    assert_eq!(payloads, [OwnedPayload::Symbol(b"n".to_vec()), OwnedPayload::String("t".into()), OwnedPayload::Float32(0.5)]);
+}
+
+#[test]
+fn skipped_and_default_fields() {
+    // skipped fields aren't written and decode to their default.
+    let user = User {
+        name:     "bob".into(),
+        id:       1,
+        cache:    vec![1, 2, 3],
+        nickname: None,
+        location: Point { x: 0, y: 0 },
+        scores:   BTreeMap::new(),
+    };
+    let bytes = codec::encode(&user).unwrap();
+    let decoded = codec::decode::<User>(&bytes).unwrap();
+    assert_eq!(decoded.cache, Vec::<u32>::new());
+    assert_eq!(User { cache: vec![1, 2, 3], ..decoded }, user);
+
+    // `default` fields may be missing, others may not.
+    let mut encoder = Encoder::default();
+    encoder.value().kind(b"user").with_tags()
+        .tag(b"user-name", |e| e.write_string("cy"))
+        .tag(b"id", |e| e.write_nat64(2))
+        .tag(b"location", |e| Point { x: 1, y: 1 }.encode(e))
+        .tag(b"scores", |e| { e.value().with_tags().payload_null(); })
+        .tag(b"unknown", |e| e.write_null())
+        .payload_null();
+    let bytes = encoder.build().unwrap();
+    let decoded = codec::decode::<User>(&bytes).unwrap();
+    assert_eq!(decoded.nickname, None);
+    assert_eq!(decoded.name, "cy");
+
+    let mut encoder = Encoder::default();
+    encoder.value().kind(b"user").with_tags()
+        .tag(b"user-name", |e| e.write_string("cy"))
+        .payload_null();
+    let bytes = encoder.build().unwrap();
+    let error = codec::decode::<User>(&bytes).unwrap_err();
+    assert_eq!((error.reason, error.stage, error.offset), (Reason::InvalidValue(Invalid::MissingTag), Stage::Tags, 0));
+    assert_eq!(error.path, Path(vec![PathSegment::Tag(b"id".to_vec())]));
+}
+
+#[test]
+fn tuple_structs() {
+    let value = round_trip(&Tuple(3, false, "x".into()));
+    match &value.payload {
+        OwnedPayload::List(values) => assert_eq!(values.len(), 2),
+        payload => panic!("expected a list, found {:?}", payload),
+    }
+
+    let bytes = codec::encode(&(1u16, "x", 2u8)).unwrap();
+    let error = codec::decode::<Tuple>(&bytes).unwrap_err();
+    assert_eq!(error.reason, Reason::InvalidValue(Invalid::UnexpectedLength));
+    assert_eq!((error.expected, error.available), (2, 3));
+
+    let value = round_trip(&Meters(2.5));
+    assert_eq!(value.kind.as_deref(), Some(&b"meters"[..]));
+    assert_eq!(value.payload, OwnedPayload::List(vec![OwnedValue::new(OwnedPayload::Float64(2.5))]));
+
+    let value = round_trip(&Unit);
+    assert_eq!(value, OwnedValue::new(OwnedPayload::Null));
+}
+
+#[test]
+fn transparent_structs() {
+    // written as the field itself, with the field's wire type.
+    let value = round_trip(&Id(9));
+    assert_eq!(value, OwnedValue::new(OwnedPayload::Nat32(9)));
+
+    let bytes = codec::encode(&5u8).unwrap();
+    let error = codec::decode::<Id>(&bytes).unwrap_err();
+    assert_eq!(error.reason, Reason::InvalidValue(Invalid::UnexpectedType { expected: "nat32" }));
+}
+
+#[test]
+fn enum_variants() {
+    let value = round_trip(&Shape::Empty);
+    assert_eq!(value.kind.as_deref(), Some(&b"Empty"[..]));
+    assert_eq!(value.payload, OwnedPayload::Null);
+
+    let value = round_trip(&Shape::Circle(1.0));
+    assert_eq!(value.kind.as_deref(), Some(&b"Circle"[..]));
+    assert_eq!(value.payload, OwnedPayload::List(vec![OwnedValue::new(OwnedPayload::Float64(1.0))]));
+
+    let value = round_trip(&Shape::Rectangle(1.0, 2.0));
+    assert_eq!(value.kind.as_deref(), Some(&b"rect"[..]));
+
+    let value = round_trip(&Shape::Polygon { points: vec![Point { x: 0, y: 0 }, Point { x: 1, y: 0 }], closed: true });
+    assert_eq!(value.kind.as_deref(), Some(&b"Polygon"[..]));
+    assert_eq!(tags(&value), [&b"points"[..], b"closed?"]);
+
+    let mut encoder = Encoder::default();
+    encoder.value().kind(b"Square").payload_null();
+    let bytes = encoder.build().unwrap();
+    let error = codec::decode::<Shape>(&bytes).unwrap_err();
+    assert_eq!((error.reason, error.stage), (Reason::InvalidValue(Invalid::UnexpectedKind), Stage::Kind));
+
+    let bytes = codec::encode(&()).unwrap();
+    let error = codec::decode::<Shape>(&bytes).unwrap_err();
+    assert_eq!(error.reason, Reason::InvalidValue(Invalid::UnexpectedKind));
+}
+
+#[test]
+fn generics() {
+    round_trip(&Pair { first: 1u64, second: "two".to_string() });
+    round_trip(&Pair { first: Pair { first: -1i8, second: vec![true] }, second: Shape::Empty });
+
+    let bytes = codec::encode(&Borrowed { name: "name", bytes: b"\x00\x01" }).unwrap();
+    let decoded = codec::decode::<Borrowed>(&bytes).unwrap();
+    assert_eq!(decoded, Borrowed { name: "name", bytes: b"\x00\x01" });
+    // zero copy.
+    let range = bytes.as_ptr_range();
+    assert!(range.contains(&decoded.name.as_ptr()));
+    assert!(range.contains(&decoded.bytes.as_ptr()));
+}
+
+#[test]
+fn error_paths() {
+    let mut encoder = Encoder::default();
+    encoder.value().kind(b"Polygon").with_tags()
+        .tag(b"points", |e| {
+            e.value().payload_list(2, |e| {
+                Point { x: 0, y: 0 }.encode(e);
+                e.value().with_tags()
+                    .tag(b"x", |e| e.write_signed(1))
+                    .tag(b"y", |e| e.write_string("one"))
+                    .payload_null();
+            });
+        })
+        .tag(b"closed?", |e| e.write_bool(false))
+        .payload_null();
+    let bytes = encoder.build().unwrap();
+
+    let error = codec::decode::<Shape>(&bytes).unwrap_err();
+    assert_eq!(error.reason, Reason::InvalidValue(Invalid::UnexpectedType { expected: "integer" }));
+    assert_eq!(error.header, Some(bytes[error.offset]));
+    assert_eq!(error.path, Path(vec![
+        PathSegment::Tag(b"points".to_vec()),
+        PathSegment::Index(1),
+        PathSegment::Tag(b"y".to_vec()),
+    ]));
+    assert_eq!(error.to_string(), format!(
+        "InvalidValue(UnexpectedType {{ expected: \"integer\" }}) at offset {} while decoding Payload of String at $.points[1].y",
+        error.offset));
+
+    // tuple fields are reported by their index on the wire, skipped fields
+    // don't count.
+    let mut encoder = Encoder::default();
+    encoder.value().payload_list(2, |e| {
+        e.write_unsigned(1);
+        e.write_unsigned(2);
+    });
+    let bytes = encoder.build().unwrap();
+    let error = codec::decode::<Tuple>(&bytes).unwrap_err();
+    assert_eq!(error.path, Path(vec![PathSegment::Index(1)]));
+}
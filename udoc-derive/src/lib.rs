@@ -0,0 +1,584 @@
+use proc_macro2::{TokenStream, Literal, Span};
+use quote::{quote, format_ident};
+use syn::{parse_macro_input, DeriveInput, Data, Fields, Field, Ident, LitStr, Type, Generics, GenericParam, Lifetime, LifetimeParam};
+
+
+#[proc_macro_derive(UdocEncode, attributes(udoc))]
+pub fn derive_encode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    encode(&input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+#[proc_macro_derive(UdocDecode, attributes(udoc))]
+pub fn derive_decode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    decode(&input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+
+
+// `#[udoc(kind = "...")]` on structs and enum variants.
+struct ItemAttrs {
+    kind: Option<LitStr>,
+}
+
+// `#[udoc(tag = "...", wire = "...", skip, default)]` on fields.
+struct FieldAttrs {
+    tag:     Option<LitStr>,
+    wire:    Option<Ident>,
+    skip:    bool,
+    default: bool,
+}
+
+const WIRE_TYPES: &[&str] = &[
+    "bool",
+    "nat8", "nat16", "nat32", "nat64",
+    "int8", "int16", "int32", "int64",
+    "float32", "float64",
+    "decimal32", "decimal64",
+    "string", "bytes", "symbol",
+];
+
+// the `Encoder` method and the type it takes, for a wire type.
+fn wire_write(wire: &Ident) -> (Ident, TokenStream) {
+    let method = match wire.to_string().as_str() {
+        "float32" => format_ident!("write_f32"),
+        "float64" => format_ident!("write_f64"),
+        name      => format_ident!("write_{}", name),
+    };
+    let ty = match wire.to_string().as_str() {
+        "bool"      => quote!(bool),
+        "nat8"      => quote!(u8),
+        "nat16"     => quote!(u16),
+        "nat32"     => quote!(u32),
+        "nat64"     => quote!(u64),
+        "int8"      => quote!(i8),
+        "int16"     => quote!(i16),
+        "int32"     => quote!(i32),
+        "int64"     => quote!(i64),
+        "float32"   => quote!(f32),
+        "float64"   => quote!(f64),
+        "decimal32" => quote!(::udoc::decimal::Decimal32),
+        "decimal64" => quote!(::udoc::decimal::Decimal64),
+        "string"    => quote!(str),
+        "bytes"     => quote!([u8]),
+        "symbol"    => quote!([u8]),
+        _           => unreachable!(),
+    };
+    (method, ty)
+}
+
+fn item_attrs(attrs: &[syn::Attribute]) -> syn::Result<ItemAttrs> {
+    let mut result = ItemAttrs { kind: None };
+    for attr in attrs {
+        if !attr.path().is_ident("udoc") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("kind") {
+                result.kind = Some(meta.value()?.parse()?);
+                Ok(())
+            }
+            else {
+                Err(meta.error("unknown udoc attribute"))
+            }
+        })?;
+    }
+    Ok(result)
+}
+
+fn field_attrs(field: &Field) -> syn::Result<FieldAttrs> {
+    let mut result = FieldAttrs { tag: None, wire: None, skip: false, default: false };
+    for attr in &field.attrs {
+        if !attr.path().is_ident("udoc") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                if field.ident.is_none() {
+                    return Err(meta.error("`tag` requires a named field"));
+                }
+                result.tag = Some(meta.value()?.parse()?);
+            }
+            else if meta.path.is_ident("wire") {
+                let wire: LitStr = meta.value()?.parse()?;
+                if !WIRE_TYPES.contains(&wire.value().as_str()) {
+                    let message = format!("unknown wire type, expected one of: {}", WIRE_TYPES.join(", "));
+                    return Err(syn::Error::new(wire.span(), message));
+                }
+                result.wire = Some(Ident::new(&wire.value(), wire.span()));
+            }
+            else if meta.path.is_ident("skip") {
+                result.skip = true;
+            }
+            else if meta.path.is_ident("default") {
+                if field.ident.is_none() {
+                    return Err(meta.error("`default` requires a named field"));
+                }
+                result.default = true;
+            }
+            else {
+                return Err(meta.error("unknown udoc attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(result)
+}
+
+
+struct FieldInfo<'a> {
+    field:  &'a Field,
+    attrs:  FieldAttrs,
+    // `self.name` or `self.0` for structs, the binding for enum variants.
+    access: TokenStream,
+    // the name of the local the decoded value is stored in.
+    local:  Ident,
+}
+
+impl<'a> FieldInfo<'a> {
+    fn symbol(&self) -> Literal {
+        let symbol = match (&self.attrs.tag, &self.field.ident) {
+            (Some(tag), _)      => tag.value(),
+            (None, Some(ident)) => ident.to_string(),
+            (None, None)        => unreachable!(),
+        };
+        Literal::byte_string(symbol.as_bytes())
+    }
+
+    fn ty(&self) -> &Type {
+        &self.field.ty
+    }
+}
+
+fn field_infos(fields: &Fields, is_variant: bool) -> syn::Result<Vec<FieldInfo<'_>>> {
+    let mut result = vec![];
+    for (index, field) in fields.iter().enumerate() {
+        let local = format_ident!("__f{}", index);
+        let access =
+            if is_variant {
+                quote!(#local)
+            }
+            else {
+                match &field.ident {
+                    Some(ident) => quote!(self.#ident),
+                    None => {
+                        let index = syn::Index::from(index);
+                        quote!(self.#index)
+                    },
+                }
+            };
+        result.push(FieldInfo { field, attrs: field_attrs(field)?, access, local });
+    }
+    Ok(result)
+}
+
+fn kind_literal(kind: &Option<LitStr>) -> Option<Literal> {
+    kind.as_ref().map(|kind| Literal::byte_string(kind.value().as_bytes()))
+}
+
+// the kind of each variant, which must be unique.
+fn variant_kinds(data: &syn::DataEnum) -> syn::Result<Vec<Literal>> {
+    let mut seen = std::collections::HashMap::new();
+    let mut result = vec![];
+    for variant in &data.variants {
+        let attrs = item_attrs(&variant.attrs)?;
+        let kind  = match &attrs.kind {
+            Some(kind) => kind.value(),
+            None       => variant.ident.to_string(),
+        };
+        if let Some(other) = seen.insert(kind.clone(), &variant.ident) {
+            let span = attrs.kind.as_ref().map_or(variant.ident.span(), |kind| kind.span());
+            return Err(syn::Error::new(span, format!("duplicate kind {:?}, also used by `{}`", kind, other)));
+        }
+        result.push(Literal::byte_string(kind.as_bytes()));
+    }
+    Ok(result)
+}
+
+// single field tuple structs without a kind are written as their field.
+fn is_transparent(fields: &Fields, infos: &[FieldInfo], kind: &Option<Literal>) -> bool {
+    matches!(fields, Fields::Unnamed(_)) && infos.len() == 1 && !infos[0].attrs.skip && kind.is_none()
+}
+
+
+
+fn encode_field(info: &FieldInfo, value: TokenStream) -> TokenStream {
+    match &info.attrs.wire {
+        Some(wire) => {
+            let (method, ty) = wire_write(wire);
+            match wire.to_string().as_str() {
+                "string" | "bytes" | "symbol" => quote!(__encoder.#method(::core::convert::AsRef::<#ty>::as_ref(#value))),
+                _ => quote!(__encoder.#method(::core::convert::Into::<#ty>::into(::core::clone::Clone::clone(#value)))),
+            }
+        },
+        None => quote!(::udoc::codec::Encode::encode(#value, __encoder)),
+    }
+}
+
+// `reference` is true if `info.access` is already a reference.
+fn encode_fields(fields: &Fields, infos: &[FieldInfo], kind: Option<Literal>, reference: bool) -> TokenStream {
+    let value = |info: &FieldInfo| {
+        let access = &info.access;
+        if reference { quote!(#access) } else { quote!(&#access) }
+    };
+
+    let with_kind = kind.as_ref().map(|kind| quote!(.kind(#kind)));
+    let infos = infos.iter().filter(|info| !info.attrs.skip).collect::<Vec<_>>();
+
+    match fields {
+        Fields::Named(_) => {
+            let tags = infos.iter().map(|info| {
+                let symbol = info.symbol();
+                let encode = encode_field(info, value(info));
+                quote!(__builder = __builder.tag(#symbol, |__encoder| #encode);)
+            });
+            if infos.is_empty() {
+                return quote!(__encoder.value() #with_kind .with_tags().payload_null(););
+            }
+            quote! {
+                let mut __builder = __encoder.value() #with_kind .with_tags();
+                #(#tags)*
+                __builder.payload_null();
+            }
+        },
+
+        Fields::Unnamed(_) => {
+            let length = infos.len();
+            let values = infos.iter().map(|info| {
+                let encode = encode_field(info, value(info));
+                quote!(#encode;)
+            });
+            quote! {
+                __encoder.value() #with_kind .payload_list(#length, |__encoder| {
+                    #(#values)*
+                });
+            }
+        },
+
+        Fields::Unit => {
+            quote!(__encoder.value() #with_kind .payload_null();)
+        },
+    }
+}
+
+fn encode(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let name  = &input.ident;
+    let attrs = item_attrs(&input.attrs)?;
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let infos = field_infos(&data.fields, false)?;
+            let kind  = kind_literal(&attrs.kind);
+
+            if is_transparent(&data.fields, &infos, &kind) {
+                let access = &infos[0].access;
+                let encode = encode_field(&infos[0], quote!(&#access));
+                quote!(#encode;)
+            }
+            else {
+                encode_fields(&data.fields, &infos, kind, false)
+            }
+        },
+
+        Data::Enum(data) => {
+            if let Some(kind) = &attrs.kind {
+                return Err(syn::Error::new(kind.span(), "`kind` is not supported on enums, use it on the variants"));
+            }
+
+            let kinds = variant_kinds(data)?;
+            let arms = data.variants.iter().zip(kinds).map(|(variant, kind)| {
+                let ident = &variant.ident;
+                let infos = field_infos(&variant.fields, true)?;
+
+                let bindings = infos.iter().map(|info| {
+                    let local = &info.local;
+                    match &info.field.ident {
+                        Some(field) => quote!(#field: #local),
+                        None        => quote!(#local),
+                    }
+                });
+                let pattern = match &variant.fields {
+                    Fields::Named(_)   => quote!(Self::#ident { #(#bindings),* }),
+                    Fields::Unnamed(_) => quote!(Self::#ident ( #(#bindings),* )),
+                    Fields::Unit       => quote!(Self::#ident),
+                };
+
+                let body = encode_fields(&variant.fields, &infos, Some(kind), true);
+                Ok(quote!(#pattern => { #body }))
+            }).collect::<syn::Result<Vec<_>>>()?;
+
+            if arms.is_empty() {
+                quote!(match *self {})
+            }
+            else {
+                quote! {
+                    #[allow(unused_variables)]
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        },
+
+        Data::Union(_) => {
+            return Err(syn::Error::new(Span::call_site(), "unions are not supported"));
+        },
+    };
+
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(::udoc::codec::Encode));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::udoc::codec::Encode for #name #ty_generics #where_clause {
+            fn encode(&self, __encoder: &mut ::udoc::encoder::Encoder) {
+                #body
+            }
+        }
+    })
+}
+
+
+
+fn decode_field(info: &FieldInfo, value: TokenStream) -> TokenStream {
+    let ty = info.ty();
+    match &info.attrs.wire {
+        Some(wire) => quote!(::udoc::codec::wire::#wire::<#ty>(&#value)),
+        None       => quote!(<#ty as ::udoc::codec::Decode<'de>>::decode(&#value)),
+    }
+}
+
+// evaluates to the constructor arguments, `{ a: .., b: .. }` or `(.., ..)`.
+fn decode_fields(fields: &Fields, infos: &[FieldInfo], kind: Option<Literal>, constructor: TokenStream) -> TokenStream {
+    let check_kind = kind.as_ref().map(|kind| quote! {
+        if !__value.header.has_kind || __value.kind != #kind {
+            return ::core::result::Result::Err(::udoc::decoder::DecodeError::unexpected_kind(__value));
+        }
+    });
+
+    match fields {
+        Fields::Named(_) => {
+            let locals = infos.iter().filter(|info| !info.attrs.skip).map(|info| {
+                let local = &info.local;
+                let ty    = info.ty();
+                quote!(let mut #local: ::core::option::Option<#ty> = ::core::option::Option::None;)
+            });
+
+            let arms = infos.iter().filter(|info| !info.attrs.skip).map(|info| {
+                let local  = &info.local;
+                let symbol = info.symbol();
+                let decode = decode_field(info, quote!(__tag));
+                quote! {
+                    #symbol => {
+                        #local = ::core::option::Option::Some(#decode.map_err(|e| e.in_tag(__symbol))?);
+                    },
+                }
+            }).collect::<Vec<_>>();
+
+            let tags =
+                if arms.is_empty() {
+                    quote!(for _ in &mut __tags {})
+                }
+                else {
+                    quote! {
+                        for (__symbol, __tag) in &mut __tags {
+                            // note: unknown tags are ignored.
+                            match __symbol {
+                                #(#arms)*
+                                _ => (),
+                            }
+                        }
+                    }
+                };
+
+            let values = infos.iter().map(|info| {
+                let ident = &info.field.ident;
+                let local = &info.local;
+                if info.attrs.skip {
+                    quote!(#ident: ::core::default::Default::default())
+                }
+                else if info.attrs.default {
+                    quote!(#ident: #local.unwrap_or_default())
+                }
+                else {
+                    let symbol = info.symbol();
+                    quote!(#ident: #local.ok_or_else(|| ::udoc::decoder::DecodeError::missing_tag(__value, #symbol))?)
+                }
+            });
+
+            quote! {
+                #check_kind
+                if !__value.header.has_tags || !::core::matches!(__value.payload, ::udoc::decoder::Payload::Null) {
+                    return ::core::result::Result::Err(::udoc::decoder::DecodeError::unexpected_type("null with tags", __value));
+                }
+                #(#locals)*
+                let mut __tags = __value.tags()?;
+                #tags
+                __tags.check_error()?;
+                ::core::result::Result::Ok(#constructor { #(#values),* })
+            }
+        },
+
+        Fields::Unnamed(_) => {
+            let length = infos.iter().filter(|info| !info.attrs.skip).count();
+
+            // the length is checked up front, so `next` only ends early if an
+            // element fails to decode.
+            let elements = infos.iter().filter(|info| !info.attrs.skip).enumerate().map(|(index, info)| {
+                let local  = &info.local;
+                let decode = decode_field(info, quote!(__element));
+                quote! {
+                    let #local = match __list.next() {
+                        ::core::option::Option::Some(__element) => #decode.map_err(|e| e.in_index(#index))?,
+                        ::core::option::Option::None => {
+                            return ::core::result::Result::Err(__list.take_element_error()
+                                .unwrap_or_else(|| ::udoc::decoder::DecodeError::unexpected_length(__value, #length, #index)));
+                        },
+                    };
+                }
+            });
+
+            let values = infos.iter().map(|info| {
+                let local = &info.local;
+                if info.attrs.skip { quote!(::core::default::Default::default()) } else { quote!(#local) }
+            });
+
+            quote! {
+                #check_kind
+                let __list = match __value.payload {
+                    ::udoc::decoder::Payload::List (__list) if !__value.header.has_tags => __list,
+                    _ => return ::core::result::Result::Err(::udoc::decoder::DecodeError::unexpected_type("list", __value)),
+                };
+                let mut __list = ::udoc::decoder::ListDecoder::new(__list, __value.document, __value.depth)?;
+                if __list.length != #length {
+                    return ::core::result::Result::Err(::udoc::decoder::DecodeError::unexpected_length(__value, #length, __list.length));
+                }
+                #(#elements)*
+                __list.check_error()?;
+                ::core::result::Result::Ok(#constructor ( #(#values),* ))
+            }
+        },
+
+        Fields::Unit => {
+            quote! {
+                #check_kind
+                match __value.payload {
+                    ::udoc::decoder::Payload::Null if !__value.header.has_tags => ::core::result::Result::Ok(#constructor),
+                    _ => ::core::result::Result::Err(::udoc::decoder::DecodeError::unexpected_type("null", __value)),
+                }
+            }
+        },
+    }
+}
+
+// adds `'de`, outliving all lifetimes of the type.
+fn decode_generics(generics: &Generics) -> Generics {
+    let de = Lifetime::new("'de", Span::call_site());
+
+    let mut result = generics.clone();
+    for param in result.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(::udoc::codec::Decode<#de>));
+    }
+
+    let mut param = LifetimeParam::new(de);
+    for lifetime in generics.lifetimes() {
+        param.bounds.push(lifetime.lifetime.clone());
+    }
+    result.params.insert(0, GenericParam::Lifetime(param));
+    result
+}
+
+fn decode(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let name  = &input.ident;
+    let attrs = item_attrs(&input.attrs)?;
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let infos = field_infos(&data.fields, false)?;
+            let kind  = kind_literal(&attrs.kind);
+
+            if is_transparent(&data.fields, &infos, &kind) {
+                let decode = decode_field(&infos[0], quote!((*__value)));
+                quote!(::core::result::Result::Ok(Self(#decode?)))
+            }
+            else {
+                decode_fields(&data.fields, &infos, kind, quote!(Self))
+            }
+        },
+
+        Data::Enum(data) => {
+            if let Some(kind) = &attrs.kind {
+                return Err(syn::Error::new(kind.span(), "`kind` is not supported on enums, use it on the variants"));
+            }
+
+            let kinds = variant_kinds(data)?;
+            let arms = data.variants.iter().zip(kinds).map(|(variant, kind)| {
+                let ident = &variant.ident;
+                let infos = field_infos(&variant.fields, true)?;
+
+                // note: the kind was matched already.
+                let body = decode_fields(&variant.fields, &infos, None, quote!(Self::#ident));
+                Ok(quote!(#kind if __value.header.has_kind => { #body },))
+            }).collect::<syn::Result<Vec<_>>>()?;
+
+            quote! {
+                match __value.kind {
+                    #(#arms)*
+                    _ => ::core::result::Result::Err(::udoc::decoder::DecodeError::unexpected_kind(__value)),
+                }
+            }
+        },
+
+        Data::Union(_) => {
+            return Err(syn::Error::new(Span::call_site(), "unions are not supported"));
+        },
+    };
+
+    let generics = decode_generics(&input.generics);
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::udoc::codec::Decode<'de> for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn decode(__value: &::udoc::decoder::Value<'de>) -> ::core::result::Result<Self, ::udoc::decoder::DecodeError> {
+                #body
+            }
+        }
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_kinds() {
+        let input: DeriveInput = syn::parse_quote! {
+            enum Shape {
+                Circle,
+                #[udoc(kind = "Circle")]
+                Round,
+            }
+        };
+        let error = decode(&input).unwrap_err();
+        assert_eq!(error.to_string(), "duplicate kind \"Circle\", also used by `Circle`");
+        assert_eq!(encode(&input).unwrap_err().to_string(), error.to_string());
+
+        let input: DeriveInput = syn::parse_quote! {
+            enum Shape {
+                #[udoc(kind = "x")]
+                Circle,
+                #[udoc(kind = "y")]
+                Round,
+            }
+        };
+        assert!(decode(&input).is_ok());
+    }
+}